@@ -0,0 +1,332 @@
+use crate::command::run_command;
+use crate::document::{Document, Occurrence};
+use crate::error::{KugiriError, Result};
+use crate::insert::insert_document;
+use crate::io::read_file;
+use crate::markers::MarkerSyntax;
+use crate::remove::remove_occurrences;
+use crate::update::update_occurrences;
+use crate::upsert::upsert_document;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// A list of edits for `kugiri apply`, read from YAML or JSON:
+///
+/// ```yaml
+/// operations:
+///   - file: CHANGELOG.md
+///     op: insert
+///     id: v1.4.0
+///     after: CHANGELOG-TOP
+///     body_file: notes.md
+///   - file: README.md
+///     op: update
+///     id: cli-help
+///     command: kugiri --help
+/// ```
+///
+/// Paths and commands are relative to the manifest's directory.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Operation {
+    pub file: String,
+    pub op: OperationKind,
+    pub id: String,
+    /// Anchor for `insert` and `upsert`
+    pub before: Option<String>,
+    pub after: Option<String>,
+    /// Literal content
+    pub body: Option<String>,
+    /// File to read the content from
+    pub body_file: Option<String>,
+    /// Shell command whose output is the content
+    pub command: Option<String>,
+    #[serde(default)]
+    pub allow_duplicate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    Insert,
+    Update,
+    Upsert,
+    Remove,
+}
+
+/// The new content of a file edited by a manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    pub original: String,
+    pub result: String,
+}
+
+impl Manifest {
+    /// Parse a manifest; JSON when `path` ends in `.json`, YAML otherwise.
+    pub fn parse(text: &str, path: &str) -> Result<Self> {
+        let parsed = if path.ends_with(".json") {
+            serde_json::from_str(text).map_err(|err| err.to_string())
+        } else {
+            serde_yaml::from_str(text).map_err(|err| err.to_string())
+        };
+        let manifest: Manifest = parsed
+            .map_err(|err| KugiriError::invalid(format!("Invalid manifest {path}: {err}")))?;
+        for (index, operation) in manifest.operations.iter().enumerate() {
+            operation.validate().map_err(|err| {
+                KugiriError::invalid(format!(
+                    "Invalid manifest {path}: operation {}: {err}",
+                    index + 1
+                ))
+            })?;
+        }
+        Ok(manifest)
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        Manifest::parse(&read_file(path)?, path)
+    }
+
+    /// Apply every operation in memory, reading each file once and applying
+    /// its operations in manifest order, each to the result of the previous
+    /// one. Paths naming the same file, such as `README.md` and
+    /// `./README.md`, share one change. Nothing is written: the caller gets
+    /// one change per file, in order of first mention, or the first error.
+    pub fn plan(
+        &self,
+        base: &Path,
+        syntax_for: impl Fn(&str) -> Result<MarkerSyntax>,
+    ) -> Result<Vec<FileChange>> {
+        let mut changes: Vec<(FileChange, MarkerSyntax, PathBuf)> = Vec::new();
+
+        for (index, operation) in self.operations.iter().enumerate() {
+            let path = base.join(&operation.file).to_string_lossy().into_owned();
+            let key = Path::new(&path)
+                .canonicalize()
+                .map_err(|err| KugiriError::io(&path, err))?;
+            let position = match changes.iter().position(|(_, _, known)| *known == key) {
+                Some(position) => position,
+                None => {
+                    let original = read_file(&path)?;
+                    let change = FileChange {
+                        path: path.clone(),
+                        result: original.clone(),
+                        original,
+                    };
+                    changes.push((change, syntax_for(&path)?, key));
+                    changes.len() - 1
+                }
+            };
+
+            let (change, syntax, _) = &mut changes[position];
+            change.result = operation
+                .apply(&change.result, syntax, base)
+                .map_err(|err| KugiriError::Operation {
+                    index,
+                    path: path.clone(),
+                    source: Box::new(err),
+                })?;
+        }
+
+        Ok(changes.into_iter().map(|(change, _, _)| change).collect())
+    }
+}
+
+impl Operation {
+    fn validate(&self) -> std::result::Result<(), String> {
+        let sources = [&self.body, &self.body_file, &self.command]
+            .iter()
+            .filter(|source| source.is_some())
+            .count();
+        match (self.op, sources) {
+            (OperationKind::Remove, 0) => {}
+            (OperationKind::Remove, _) => {
+                return Err("remove takes no body, body_file or command".to_string())
+            }
+            (_, 1) => {}
+            (_, _) => return Err("specify exactly one of body, body_file or command".to_string()),
+        }
+        if self.before.is_some() && self.after.is_some() {
+            return Err("specify only one of before or after".to_string());
+        }
+        let anchored = matches!(self.op, OperationKind::Insert | OperationKind::Upsert);
+        if !anchored && (self.before.is_some() || self.after.is_some()) {
+            return Err("before and after only apply to insert and upsert".to_string());
+        }
+        Ok(())
+    }
+
+    fn content(&self, base: &Path) -> Result<String> {
+        if let Some(body) = &self.body {
+            return Ok(body.clone());
+        }
+        if let Some(body_file) = &self.body_file {
+            return read_file(&base.join(body_file).to_string_lossy());
+        }
+        match &self.command {
+            Some(command) => run_command(command, base),
+            None => Ok(String::new()),
+        }
+    }
+
+    fn apply(&self, text: &str, syntax: &MarkerSyntax, base: &Path) -> Result<String> {
+        let content = self.content(base)?;
+        let (before, after) = (self.before.as_deref(), self.after.as_deref());
+        match self.op {
+            OperationKind::Insert => {
+                let doc = Document::parse(text, syntax);
+                insert_document(
+                    &doc,
+                    &self.id,
+                    &content,
+                    before,
+                    after,
+                    self.allow_duplicate,
+                )
+            }
+            OperationKind::Update => {
+                update_occurrences(text, &self.id, &content, Occurrence::First, syntax)
+            }
+            OperationKind::Upsert => {
+                let doc = Document::parse(text, syntax);
+                upsert_document(
+                    &doc,
+                    &self.id,
+                    &content,
+                    before,
+                    after,
+                    self.allow_duplicate,
+                )
+            }
+            OperationKind::Remove => remove_occurrences(text, &self.id, Occurrence::First, syntax),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_parse_manifest() {
+        let yaml = "operations:\n  - file: README.md\n    op: update\n    id: help\n    body: |\n      text\n";
+        let manifest = Manifest::parse(yaml, "kugiri.yaml").unwrap();
+        assert_eq!(manifest.operations[0].op, OperationKind::Update);
+        assert_eq!(manifest.operations[0].body.as_deref(), Some("text\n"));
+
+        let json = r#"{"operations": [{"file": "a.md", "op": "remove", "id": "old"}]}"#;
+        let manifest = Manifest::parse(json, "kugiri.json").unwrap();
+        assert_eq!(manifest.operations[0].op, OperationKind::Remove);
+    }
+
+    #[test]
+    fn test_parse_manifest_errors() {
+        let err = |text: &str| {
+            Manifest::parse(text, "kugiri.yaml")
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(err("operations:\n  - {file: a.md, op: rename, id: x}").contains("unknown variant"));
+        assert!(err("operations:\n  - {file: a.md, op: update, id: x}").contains("exactly one"));
+        assert!(
+            err("operations:\n  - {file: a.md, op: remove, id: x, body: y}")
+                .contains("remove takes no body")
+        );
+        assert!(
+            err("operations:\n  - {file: a.md, op: update, id: x, body: y, after: z}")
+                .contains("operation 1: before and after only apply")
+        );
+        assert!(
+            err("operations:\n  - {file: a.md, op: update, id: x, text: y}")
+                .contains("unknown field")
+        );
+    }
+
+    #[test]
+    fn test_plan_applies_operations_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("CHANGELOG.md"),
+            "<!-- KUGIRI-INSERT: top -->\n<!-- KUGIRI-BEGIN: v1 -->\none\n<!-- KUGIRI-END: v1 -->\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("notes.md"), "two\n").unwrap();
+
+        let manifest = Manifest::parse(
+            "operations:
+  - {file: CHANGELOG.md, op: insert, id: v2, after: top, body_file: notes.md}
+  - {file: CHANGELOG.md, op: update, id: v2, body: TWO}
+  - {file: CHANGELOG.md, op: remove, id: v1}
+",
+            "kugiri.yaml",
+        )
+        .unwrap();
+        let changes = manifest
+            .plan(dir.path(), |path| Ok(MarkerSyntax::for_path(path)))
+            .unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].result,
+            "<!-- KUGIRI-INSERT: top -->\n\n<!-- KUGIRI-BEGIN: v2 -->\nTWO\n<!-- KUGIRI-END: v2 -->\n"
+        );
+    }
+
+    #[test]
+    fn test_plan_merges_paths_to_the_same_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(
+            dir.path().join("README.md"),
+            "<!-- KUGIRI-BEGIN: a -->\n<!-- KUGIRI-END: a -->\n<!-- KUGIRI-BEGIN: b -->\n<!-- KUGIRI-END: b -->\n",
+        )
+        .unwrap();
+
+        let manifest = Manifest::parse(
+            "operations:
+  - {file: README.md, op: update, id: a, body: one}
+  - {file: ./README.md, op: update, id: b, body: two}
+  - {file: docs/../README.md, op: remove, id: a}
+",
+            "kugiri.yaml",
+        )
+        .unwrap();
+        let changes = manifest
+            .plan(dir.path(), |path| Ok(MarkerSyntax::for_path(path)))
+            .unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].result,
+            "<!-- KUGIRI-BEGIN: b -->\ntwo\n<!-- KUGIRI-END: b -->\n"
+        );
+    }
+
+    #[test]
+    fn test_plan_reports_failing_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "text\n").unwrap();
+
+        let manifest = Manifest::parse(
+            "operations:\n  - {file: a.md, op: remove, id: gone}",
+            "m.yaml",
+        )
+        .unwrap();
+        let err = manifest
+            .plan(dir.path(), |path| Ok(MarkerSyntax::for_path(path)))
+            .unwrap_err();
+
+        assert_eq!(err.exit_code(), KugiriError::EXIT_NOT_FOUND);
+        assert!(err
+            .to_string()
+            .ends_with("a.md: Section with id 'gone' not found"));
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+
+/// `key=value` metadata written after the id in a BEGIN marker.
+pub type Attributes = BTreeMap<String, String>;
+
+/// Parse attribute text such as `lang=rust generated owner="docs team"`.
+///
+/// Values may be double-quoted to include whitespace (`\"` and `\\` escape
+/// inside quotes). A bare key is treated as `key=true`. Parsing is lenient:
+/// tokens without a key are skipped so that a malformed attribute never hides
+/// the marker itself.
+pub fn parse_attributes(text: &str) -> Attributes {
+    let mut attributes = Attributes::new();
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+
+        let value = if chars.next_if_eq(&'=').is_some() {
+            let mut value = String::new();
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        _ => value.push(c),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    value.push(c);
+                }
+            }
+            value
+        } else {
+            "true".to_string()
+        };
+
+        if !key.is_empty() {
+            attributes.insert(key, value);
+        }
+    }
+
+    attributes
+}
+
+/// Render attributes back into marker text, quoting values when needed.
+pub fn format_attributes(attributes: &Attributes) -> String {
+    attributes
+        .iter()
+        .map(|(key, value)| {
+            let needs_quotes = value.is_empty()
+                || value
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == '"' || c == '\\');
+            if needs_quotes {
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("{key}=\"{escaped}\"")
+            } else {
+                format!("{key}={value}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Set `key` in raw attribute text as written in a marker, replacing its
+/// value in place or appending it, so that the other attributes keep their
+/// order and quoting.
+pub fn set_attribute_text(text: &str, key: &str, value: &str) -> String {
+    let rendered = format_attributes(&Attributes::from([(key.to_string(), value.to_string())]));
+    for (start, end) in token_spans(text) {
+        let token = &text[start..end];
+        if token.split('=').next() == Some(key) {
+            // Keep quotes the value was written with
+            let rendered = match rendered.strip_prefix(&format!("{key}=")) {
+                Some(value) if token[key.len()..].starts_with("=\"") && !value.starts_with('"') => {
+                    format!("{key}=\"{value}\"")
+                }
+                _ => rendered,
+            };
+            return format!("{}{rendered}{}", &text[..start], &text[end..]);
+        }
+    }
+    if text.trim().is_empty() {
+        rendered
+    } else {
+        format!("{} {rendered}", text.trim_end())
+    }
+}
+
+/// Attribute text with single spaces between tokens, keeping each token as
+/// written.
+pub fn normalize_attribute_text(text: &str) -> String {
+    token_spans(text)
+        .into_iter()
+        .map(|(start, end)| &text[start..end])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Byte ranges of the whitespace-separated tokens of attribute text, with
+/// quoted values kept whole.
+fn token_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+        } else if c.is_whitespace() {
+            if let Some(start) = start.take() {
+                spans.push((start, i));
+            }
+        } else {
+            start.get_or_insert(i);
+            if c == '"' {
+                quoted = true;
+            }
+        }
+    }
+    if let Some(start) = start {
+        spans.push((start, text.len()));
+    }
+    spans
+}
+
+/// Parse a `key=value` filter as given on the command line.
+pub fn parse_attribute_filter(filter: &str) -> Option<(String, String)> {
+    let (key, value) = filter.split_once('=')?;
+    if key.is_empty() {
+        return None;
+    }
+    Some((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let attributes = parse_attributes("lang=rust generated owner=\"docs team\"");
+        assert_eq!(attributes.len(), 3);
+        assert_eq!(attributes["lang"], "rust");
+        assert_eq!(attributes["generated"], "true");
+        assert_eq!(attributes["owner"], "docs team");
+    }
+
+    #[test]
+    fn test_parse_attributes_escapes_and_garbage() {
+        let attributes = parse_attributes(r#"cmd="echo \"hi\"" =orphan  empty="""#);
+        assert_eq!(attributes["cmd"], "echo \"hi\"");
+        assert_eq!(attributes["empty"], "");
+        assert_eq!(attributes.len(), 2);
+
+        assert!(parse_attributes("   ").is_empty());
+    }
+
+    #[test]
+    fn test_format_attributes_round_trip() {
+        let attributes = parse_attributes(r#"b=2 a="x y" c="say \"hi\"""#);
+        let formatted = format_attributes(&attributes);
+        assert_eq!(formatted, r#"a="x y" b=2 c="say \"hi\"""#);
+        assert_eq!(parse_attributes(&formatted), attributes);
+    }
+
+    #[test]
+    fn test_parse_attribute_filter() {
+        assert_eq!(
+            parse_attribute_filter("lang=rust"),
+            Some(("lang".to_string(), "rust".to_string()))
+        );
+        assert_eq!(
+            parse_attribute_filter("tag="),
+            Some(("tag".to_string(), String::new()))
+        );
+        assert_eq!(parse_attribute_filter("lang"), None);
+        assert_eq!(parse_attribute_filter("=rust"), None);
+    }
+
+    #[test]
+    fn test_set_attribute_text() {
+        assert_eq!(set_attribute_text("", "hash", "ab12"), "hash=ab12");
+        assert_eq!(
+            set_attribute_text("src=a.rs fence=rust", "hash", "ab12"),
+            "src=a.rs fence=rust hash=ab12"
+        );
+        assert_eq!(
+            set_attribute_text(r#"cmd="echo hash=x" hash=old lang=rust"#, "hash", "new"),
+            r#"cmd="echo hash=x" hash=new lang=rust"#
+        );
+        assert_eq!(
+            set_attribute_text("src=a.rs#L1-L2", "src", "a.rs#L1-L3"),
+            "src=a.rs#L1-L3"
+        );
+        assert_eq!(
+            set_attribute_text(r#"src="a.rs#L1-L2" fence=rust"#, "src", "a.rs#L1-L3"),
+            r#"src="a.rs#L1-L3" fence=rust"#
+        );
+    }
+
+    #[test]
+    fn test_normalize_attribute_text() {
+        assert_eq!(
+            normalize_attribute_text(r#"  lang=rust   cmd="echo  a"  "#),
+            r#"lang=rust cmd="echo  a""#
+        );
+        assert_eq!(normalize_attribute_text(""), "");
+    }
+}
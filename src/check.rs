@@ -0,0 +1,264 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::document::{Document, MarkerProblem, NodeKind, SectionNode};
+use crate::error::KugiriError;
+use crate::utils::join_lines_preserve_trailing_newline;
+use std::collections::BTreeMap;
+
+/// Characters that have a meaning in selectors and so can't be selected
+/// literally when they appear in an id.
+const SELECTOR_CHARS: &[char] = &['/', '[', ']', '*', '?', '=', '"'];
+
+/// Lint the markers of `doc`, returning one diagnostic per problem ordered
+/// by line:
+///
+/// - `unclosed-begin`, `orphan-end`, `crossed-markers`, `indent-mismatch`:
+///   the marker problems found while parsing
+/// - `duplicate-id`: several sections with the same id
+/// - `insert-id-conflict`: an INSERT marker with the id of a section, which
+///   `--before`/`--after` never pick
+/// - `indented-marker` (a warning): markers that `kugiri trim` keeps because
+///   they don't start at column 0, unless `indent-mismatch` already applies
+/// - `invalid-id`: ids that commands or selectors can't address
+pub fn check_document(doc: &Document, path: &str) -> Vec<Diagnostic> {
+    let mut findings: Vec<(usize, Diagnostic)> = Vec::new();
+
+    for problem in doc.problems() {
+        let err = KugiriError::from(problem.clone());
+        let diagnostic = Diagnostic::from_error(&err, path, doc).with_code(problem_code(problem));
+        findings.push((problem.line(), diagnostic));
+    }
+
+    let mut by_id: BTreeMap<&str, Vec<&SectionNode>> = BTreeMap::new();
+    for node in doc.sections() {
+        by_id.entry(node.id()).or_default().push(node);
+    }
+    for (id, nodes) in &by_id {
+        if let [first, second, rest @ ..] = nodes.as_slice() {
+            let mut diagnostic = at(
+                path,
+                "duplicate-id",
+                format!("Section id '{id}' is used {} times", nodes.len()),
+            );
+            diagnostic.label_node(doc, first, "first used here".to_string(), false);
+            diagnostic.label_node(doc, second, "used again here".to_string(), true);
+            for node in rest {
+                diagnostic.label_node(doc, node, "and here".to_string(), false);
+            }
+            diagnostic.hint = Some(
+                "rename the sections, or pass --occurrence N or --all to act on them".to_string(),
+            );
+            findings.push((second.section.start_line, diagnostic));
+        }
+    }
+
+    for node in doc.nodes() {
+        if node.kind == NodeKind::Insert {
+            if let Some(section) = doc.get(node.id()) {
+                let mut diagnostic = at(
+                    path,
+                    "insert-id-conflict",
+                    format!("INSERT marker '{}' has the same id as a section", node.id()),
+                );
+                diagnostic.label_node(doc, section, "section defined here".to_string(), false);
+                diagnostic.label_node(doc, node, "INSERT marker".to_string(), true);
+                diagnostic.hint = Some(format!(
+                    "--before/--after '{}' use the section, so this INSERT marker is never used; rename one of them",
+                    node.id()
+                ));
+                findings.push((node.section.start_line, diagnostic));
+            }
+        }
+
+        if let Some(diagnostic) = check_indented(doc, path, node) {
+            findings.push((node.section.start_line, diagnostic));
+        }
+
+        if let Some(reason) = invalid_id(doc, node.id()) {
+            let mut diagnostic = at(
+                path,
+                "invalid-id",
+                format!("Invalid section id '{}': {reason}", node.id()),
+            );
+            diagnostic.label_node(doc, node, "defined here".to_string(), true);
+            diagnostic.hint = Some("use letters, digits, '-', '_' and '.' in ids".to_string());
+            findings.push((node.section.start_line, diagnostic));
+        }
+    }
+
+    findings.sort_by_key(|(line, _)| *line);
+    findings
+        .into_iter()
+        .map(|(_, diagnostic)| diagnostic)
+        .collect()
+}
+
+fn at(path: &str, code: &'static str, message: String) -> Diagnostic {
+    let mut diagnostic = Diagnostic::new(message).with_code(code);
+    diagnostic.path = Some(path.to_string());
+    diagnostic
+}
+
+fn warning(path: &str, code: &'static str, message: String) -> Diagnostic {
+    let mut diagnostic = at(path, code, message);
+    diagnostic.severity = Severity::Warning;
+    diagnostic
+}
+
+fn problem_code(problem: &MarkerProblem) -> &'static str {
+    match problem {
+        MarkerProblem::Crossed { .. } => "crossed-markers",
+        MarkerProblem::OrphanEnd { .. } => "orphan-end",
+        MarkerProblem::UnclosedBegin { .. } => "unclosed-begin",
+        MarkerProblem::IndentMismatch { .. } => "indent-mismatch",
+    }
+}
+
+/// Markers on their own line that `trim_with` keeps because they are indented.
+/// Sections whose BEGIN and END markers are indented differently are left to
+/// the `indent-mismatch` error.
+fn check_indented(doc: &Document, path: &str, node: &SectionNode) -> Option<Diagnostic> {
+    if node.kind == NodeKind::Inline {
+        return None;
+    }
+    let lines = doc.lines();
+    let indent = |line: usize| {
+        let text = lines[line];
+        &text[..text.len() - text.trim_start().len()]
+    };
+    let begin_indent = indent(node.section.start_line);
+    let end_indent = indent(node.section.end_line);
+    if begin_indent.is_empty() || begin_indent != end_indent {
+        return None;
+    }
+    let end = node.kind == NodeKind::Block;
+
+    let mut diagnostic = warning(
+        path,
+        "indented-marker",
+        format!(
+            "Markers of '{}' are indented, so `kugiri trim` keeps them",
+            node.id()
+        ),
+    );
+    diagnostic.label(doc, node.section.start_line, "indented".to_string(), true);
+    if end {
+        diagnostic.label(doc, node.section.end_line, "indented".to_string(), false);
+    }
+    diagnostic.hint = Some(format!(
+        "move the markers to column 0, or remove them with `kugiri trim --select '{}'`",
+        node.id()
+    ));
+    Some(diagnostic)
+}
+
+fn invalid_id(doc: &Document, id: &str) -> Option<String> {
+    if let Err(err) = doc.syntax().validate_id(id) {
+        // Drop the "Invalid section id '...': " prefix repeated by the caller
+        let message = err.to_string();
+        return Some(match message.split_once("': ") {
+            Some((_, reason)) => reason.to_string(),
+            None => message,
+        });
+    }
+    if let Some(c) = id
+        .chars()
+        .find(|c| SELECTOR_CHARS.contains(c) || c.is_control())
+    {
+        return Some(format!("'{c}' has a special meaning in selectors"));
+    }
+    None
+}
+
+/// Repair the problems [`check_document`] reports that have a safe fix,
+/// returning the new text and the number of fixes. END markers are
+/// re-indented to match their BEGIN marker.
+pub fn fix_document(doc: &Document) -> (String, usize) {
+    let mut lines: Vec<String> = doc.lines().iter().map(|line| line.to_string()).collect();
+    let mut fixes = 0;
+
+    for problem in doc.problems() {
+        if let MarkerProblem::IndentMismatch {
+            begin_line,
+            end_line,
+            ..
+        } = problem
+        {
+            let begin = doc.lines()[*begin_line];
+            let indent = &begin[..begin.len() - begin.trim_start().len()];
+            lines[*end_line] = format!("{indent}{}", lines[*end_line].trim_start());
+            fixes += 1;
+        }
+    }
+
+    (
+        join_lines_preserve_trailing_newline(lines, doc.text()),
+        fixes,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markers::MarkerSyntax;
+
+    fn codes(text: &str) -> Vec<&'static str> {
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        check_document(&doc, "README.md")
+            .iter()
+            .filter_map(|diagnostic| diagnostic.code)
+            .collect()
+    }
+
+    #[test]
+    fn test_check_clean_document() {
+        let text =
+            "<!-- KUGIRI-INSERT: top -->\n<!-- KUGIRI-BEGIN: a -->\nx\n<!-- KUGIRI-END: a -->\n";
+        assert!(codes(text).is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_each_lint() {
+        let text = r#"<!-- KUGIRI-BEGIN: a -->
+<!-- KUGIRI-END: a -->
+<!-- KUGIRI-BEGIN: a -->
+<!-- KUGIRI-END: a -->
+<!-- KUGIRI-INSERT: a -->
+- item
+  <!-- KUGIRI-BEGIN: nested -->
+  <!-- KUGIRI-END: nested -->
+<!-- KUGIRI-BEGIN: v1/* -->
+<!-- KUGIRI-END: v1/* -->
+<!-- KUGIRI-END: orphan -->"#;
+        assert_eq!(
+            codes(text),
+            vec![
+                "duplicate-id",
+                "insert-id-conflict",
+                "indented-marker",
+                "invalid-id",
+                "orphan-end"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_marker_problems() {
+        let text = "<!-- KUGIRI-BEGIN: a -->\n  <!-- KUGIRI-END: a -->\n<!-- KUGIRI-BEGIN: b -->\n";
+        assert_eq!(codes(text), vec!["indent-mismatch", "unclosed-begin"]);
+    }
+
+    #[test]
+    fn test_fix_indent_mismatch() {
+        let text = "- item\n  <!-- KUGIRI-BEGIN: a -->\n  x\n<!-- KUGIRI-END: a -->\n";
+        let doc = Document::parse(text, &MarkerSyntax::default());
+
+        let (fixed, fixes) = fix_document(&doc);
+
+        assert_eq!(fixes, 1);
+        assert_eq!(
+            fixed,
+            "- item\n  <!-- KUGIRI-BEGIN: a -->\n  x\n  <!-- KUGIRI-END: a -->\n"
+        );
+        assert_eq!(codes(&fixed), vec!["indented-marker"]);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::error::{KugiriError, Result};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Variables every command keeps when the environment is restricted, so that
+/// the shell can still find programs.
+const BASE_ENV: &[&str] = if cfg!(windows) {
+    &[
+        "PATH",
+        "PATHEXT",
+        "SYSTEMROOT",
+        "TEMP",
+        "TMP",
+        "USERPROFILE",
+    ]
+} else {
+    &["PATH", "HOME"]
+};
+
+/// How to run a command.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOptions {
+    /// Directory to run in; the current directory when empty
+    pub cwd: PathBuf,
+    /// Kill the command when it runs longer than this
+    pub timeout: Option<Duration>,
+    /// The environment variables passed on, besides `PATH` and `HOME`;
+    /// `None` passes the whole environment
+    pub env: Option<BTreeMap<String, OsString>>,
+}
+
+/// The variables of the current environment named in `names`, for
+/// [`CommandOptions::env`]. Unset variables are left out.
+pub fn allowed_env(names: &[String]) -> BTreeMap<String, OsString> {
+    names
+        .iter()
+        .filter_map(|name| Some((name.clone(), std::env::var_os(name)?)))
+        .collect()
+}
+
+/// Run `command` with the platform shell in `cwd` and return its stdout.
+/// A non-zero exit status is an error carrying the command's stderr.
+pub fn run_command(command: &str, cwd: &Path) -> Result<String> {
+    run_command_with(
+        command,
+        &CommandOptions {
+            cwd: cwd.to_path_buf(),
+            ..CommandOptions::default()
+        },
+    )
+}
+
+/// Like [`run_command`], with a timeout and a restricted environment.
+pub fn run_command_with(command: &str, options: &CommandOptions) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if !options.cwd.as_os_str().is_empty() {
+        shell.current_dir(&options.cwd);
+    }
+    if let Some(env) = &options.env {
+        shell.env_clear();
+        for name in BASE_ENV {
+            if let Some(value) = std::env::var_os(name) {
+                shell.env(name, value);
+            }
+        }
+        shell.envs(env);
+    }
+
+    let mut child = shell.spawn().map_err(|err| KugiriError::io(command, err))?;
+    // Drain both pipes while waiting so a chatty command can't block on them
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|err| KugiriError::io(command, err))?
+        {
+            break status;
+        }
+        if options
+            .timeout
+            .is_some_and(|timeout| started.elapsed() >= timeout)
+        {
+            // The command may exit on its own in the meantime
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(KugiriError::Command(format!(
+                "Command '{command}' timed out after {}s",
+                options.timeout.unwrap_or_default().as_secs_f32()
+            )));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        let mut message = format!("Command '{command}' failed with {status}");
+        if !stderr.trim().is_empty() {
+            message.push_str(&format!(": {}", stderr.trim_end()));
+        }
+        return Err(KugiriError::Command(message));
+    }
+    String::from_utf8(stdout)
+        .map_err(|_| KugiriError::Command(format!("Command '{command}' printed invalid UTF-8")))
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement, hyperlinks) from
+/// command output.
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in '@'..='~'
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ESC '\'
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // Other escapes are two characters long
+            _ => {}
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;31merror\x1b[0m: x"), "error: x");
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07 done"),
+            "link done"
+        );
+        assert_eq!(strip_ansi("plain\n"), "plain\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command() {
+        let cwd = Path::new(".");
+        assert_eq!(run_command("echo hello", cwd).unwrap(), "hello\n");
+
+        let err = run_command("echo oops >&2; exit 3", cwd).unwrap_err();
+        assert_eq!(err.exit_code(), KugiriError::EXIT_FAILURE);
+        assert!(err.to_string().ends_with(": oops"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command_timeout() {
+        let options = CommandOptions {
+            timeout: Some(Duration::from_millis(100)),
+            ..CommandOptions::default()
+        };
+        let err = run_command_with("sleep 5", &options).unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command_env_allowlist() {
+        // PATH is always passed on, other variables only when listed
+        let options = CommandOptions {
+            env: Some(BTreeMap::from([(
+                "KUGIRI_TEST_ALLOWED".to_string(),
+                OsString::from("shown"),
+            )])),
+            ..CommandOptions::default()
+        };
+        let output = run_command_with(
+            "echo \"${PATH:+path}-$CARGO_PKG_NAME-$KUGIRI_TEST_ALLOWED\"",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(output, "path--shown\n");
+    }
+}
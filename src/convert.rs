@@ -0,0 +1,527 @@
+use crate::attributes::{format_attributes, Attributes};
+use crate::error::{KugiriError, Result};
+use crate::markers::{find_sections_with, MarkerKind, MarkerSyntax};
+use crate::utils::join_lines_preserve_trailing_newline;
+use std::fmt;
+use std::str::FromStr;
+
+/// Doc-injection tools whose regions can be converted into KUGIRI sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertFrom {
+    /// `<!-- AUTO-GENERATED-CONTENT:START (NAME:opts) -->` ... `<!-- AUTO-GENERATED-CONTENT:END -->`
+    MarkdownMagic,
+    /// `` `$ cmd` `` followed by `<!-- BEGIN mdsh -->` ... `<!-- END mdsh -->`
+    Mdsh,
+    /// `[[[cog ... ]]]` generator code, output, then `[[[end]]]`
+    Cog,
+    /// A fenced code block whose first line is a comment naming the source file
+    Embedme,
+}
+
+impl ConvertFrom {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConvertFrom::MarkdownMagic => "markdown-magic",
+            ConvertFrom::Mdsh => "mdsh",
+            ConvertFrom::Cog => "cog",
+            ConvertFrom::Embedme => "embedme",
+        }
+    }
+}
+
+impl fmt::Display for ConvertFrom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ConvertFrom {
+    type Err = KugiriError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "markdown-magic" => Ok(ConvertFrom::MarkdownMagic),
+            "mdsh" => Ok(ConvertFrom::Mdsh),
+            "cog" => Ok(ConvertFrom::Cog),
+            "embedme" => Ok(ConvertFrom::Embedme),
+            _ => Err(KugiriError::invalid(format!(
+                "Unknown tool '{s}' (expected one of: markdown-magic, mdsh, cog, embedme)"
+            ))),
+        }
+    }
+}
+
+/// A region found in the source document, converted line by line.
+struct Region {
+    id: String,
+    attributes: Attributes,
+    /// Lines replaced by the BEGIN marker, or `None` to insert it before `body_start`
+    begin_line: Option<usize>,
+    body_start: usize,
+    /// Line replaced by the END marker, or `None` to insert it after `body_end`
+    end_line: Option<usize>,
+    body_end: usize,
+    indent: String,
+}
+
+/// Rewrite the regions of another doc-injection tool into KUGIRI sections,
+/// keeping their content unchanged and their options as marker attributes.
+pub fn convert(text: &str, from: ConvertFrom, syntax: &MarkerSyntax) -> Result<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut ids = IdAllocator::new(text, syntax);
+
+    let regions = match from {
+        ConvertFrom::MarkdownMagic => markdown_magic_regions(&lines, &mut ids)?,
+        ConvertFrom::Mdsh => mdsh_regions(&lines, &mut ids)?,
+        ConvertFrom::Cog => cog_regions(&lines, &mut ids)?,
+        ConvertFrom::Embedme => embedme_regions(&lines, &mut ids),
+    };
+
+    let mut result: Vec<String> = Vec::new();
+    let mut regions = regions.into_iter().peekable();
+    let mut current: Option<Region> = None;
+
+    for (idx, &line) in lines.iter().enumerate() {
+        if current.is_none() && regions.peek().is_some_and(|r| r.first_line() == idx) {
+            current = regions.next();
+        }
+        let Some(region) = &current else {
+            result.push(line.to_string());
+            continue;
+        };
+
+        let begin = format!(
+            "{}{}",
+            region.indent,
+            syntax.marker_with_attributes(
+                MarkerKind::Begin,
+                &region.id,
+                &format_attributes(&region.attributes)
+            )
+        );
+        let end = format!(
+            "{}{}",
+            region.indent,
+            syntax.marker(MarkerKind::End, &region.id)
+        );
+
+        if region.begin_line == Some(idx) {
+            result.push(begin);
+        } else {
+            if region.begin_line.is_none() && idx == region.body_start {
+                result.push(begin);
+            }
+            if region.end_line == Some(idx) {
+                result.push(end);
+            } else {
+                result.push(line.to_string());
+                if region.end_line.is_none() && idx == region.body_end {
+                    result.push(end);
+                }
+            }
+        }
+
+        if region.last_line() == idx {
+            current = None;
+        }
+    }
+
+    Ok(join_lines_preserve_trailing_newline(result, text))
+}
+
+impl Region {
+    fn first_line(&self) -> usize {
+        self.begin_line.unwrap_or(self.body_start)
+    }
+
+    fn last_line(&self) -> usize {
+        self.end_line.unwrap_or(self.body_end)
+    }
+}
+
+/// Hands out ids that do not clash with existing sections or each other.
+struct IdAllocator {
+    used: Vec<String>,
+}
+
+impl IdAllocator {
+    fn new(text: &str, syntax: &MarkerSyntax) -> Self {
+        IdAllocator {
+            used: find_sections_with(text, syntax)
+                .into_iter()
+                .map(|section| section.id)
+                .collect(),
+        }
+    }
+
+    fn allocate(&mut self, base: &str) -> String {
+        let base: String = base
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let base = base.trim_matches('-');
+        let base = if base.is_empty() { "section" } else { base };
+
+        let mut id = base.to_string();
+        let mut n = 2;
+        while self.used.contains(&id) {
+            id = format!("{base}-{n}");
+            n += 1;
+        }
+        self.used.push(id.clone());
+        id
+    }
+
+    fn allocate_numbered(&mut self, base: &str) -> String {
+        let mut n = 1;
+        loop {
+            let id = format!("{base}-{n}");
+            if !self.used.contains(&id) {
+                self.used.push(id.clone());
+                return id;
+            }
+            n += 1;
+        }
+    }
+}
+
+fn indent_of(line: &str) -> String {
+    line[..line.len() - line.trim_start().len()].to_string()
+}
+
+fn tool_attributes(tool: ConvertFrom) -> Attributes {
+    let mut attributes = Attributes::new();
+    attributes.insert("tool".to_string(), tool.name().to_string());
+    attributes
+}
+
+fn find_closing(
+    lines: &[&str],
+    start: usize,
+    is_end: impl Fn(&str) -> bool,
+    what: &str,
+) -> Result<usize> {
+    match (start + 1..lines.len()).find(|&idx| is_end(lines[idx].trim())) {
+        Some(idx) => Ok(idx),
+        None => Err(KugiriError::invalid(format!(
+            "{what} opened at line {} is never closed",
+            start + 1
+        ))),
+    }
+}
+
+fn markdown_magic_regions(lines: &[&str], ids: &mut IdAllocator) -> Result<Vec<Region>> {
+    const START: &str = "<!-- AUTO-GENERATED-CONTENT:START";
+    const END: &str = "<!-- AUTO-GENERATED-CONTENT:END";
+
+    let mut regions = Vec::new();
+    let mut idx = 0;
+    while idx < lines.len() {
+        let trimmed = lines[idx].trim();
+        if !trimmed.starts_with(START) {
+            idx += 1;
+            continue;
+        }
+
+        // `(NAME:key=value&key2=value2)` after START
+        let spec = trimmed
+            .split_once('(')
+            .and_then(|(_, rest)| rest.split_once(')'))
+            .map(|(spec, _)| spec.trim())
+            .unwrap_or("");
+        let (name, options) = spec.split_once(':').unwrap_or((spec, ""));
+
+        let mut attributes = tool_attributes(ConvertFrom::MarkdownMagic);
+        if !name.is_empty() {
+            attributes.insert("transform".to_string(), name.to_string());
+        }
+        for option in options.split('&').filter(|o| !o.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, "true"));
+            attributes.insert(key.trim().to_string(), value.trim().to_string());
+        }
+
+        let end = find_closing(
+            lines,
+            idx,
+            |line| line.starts_with(END),
+            "markdown-magic region",
+        )?;
+        regions.push(Region {
+            id: ids.allocate(if name.is_empty() { "generated" } else { name }),
+            attributes,
+            begin_line: Some(idx),
+            body_start: idx + 1,
+            end_line: Some(end),
+            body_end: end.saturating_sub(1),
+            indent: indent_of(lines[idx]),
+        });
+        idx = end + 1;
+    }
+    Ok(regions)
+}
+
+fn mdsh_regions(lines: &[&str], ids: &mut IdAllocator) -> Result<Vec<Region>> {
+    const BEGIN: &str = "<!-- BEGIN mdsh -->";
+    const END: &str = "<!-- END mdsh -->";
+
+    let mut regions = Vec::new();
+    let mut idx = 0;
+    while idx < lines.len() {
+        if lines[idx].trim() != BEGIN {
+            idx += 1;
+            continue;
+        }
+
+        let mut attributes = tool_attributes(ConvertFrom::Mdsh);
+        // The command is the inline code span on the closest non-blank line above
+        let command = lines[..idx]
+            .iter()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line.trim())
+            .and_then(|line| line.strip_prefix('`')?.strip_suffix('`'));
+        if let Some(command) = command {
+            if let Some(cmd) = command.strip_prefix("$ ") {
+                attributes.insert("cmd".to_string(), cmd.trim().to_string());
+                attributes.insert("fence".to_string(), "text".to_string());
+            } else if let Some(cmd) = command.strip_prefix("> ") {
+                attributes.insert("cmd".to_string(), cmd.trim().to_string());
+            }
+        }
+
+        let end = find_closing(lines, idx, |line| line == END, "mdsh block")?;
+        regions.push(Region {
+            id: ids.allocate_numbered("mdsh"),
+            attributes,
+            begin_line: Some(idx),
+            body_start: idx + 1,
+            end_line: Some(end),
+            body_end: end.saturating_sub(1),
+            indent: indent_of(lines[idx]),
+        });
+        idx = end + 1;
+    }
+    Ok(regions)
+}
+
+fn cog_regions(lines: &[&str], ids: &mut IdAllocator) -> Result<Vec<Region>> {
+    let mut regions = Vec::new();
+    let mut idx = 0;
+    while idx < lines.len() {
+        if !lines[idx].contains("[[[cog") {
+            idx += 1;
+            continue;
+        }
+
+        // The generator code ends with `]]]` (possibly on the same line)
+        let code_end = if lines[idx].contains("]]]") {
+            idx
+        } else {
+            find_closing(lines, idx, |line| line.contains("]]]"), "cog generator")?
+        };
+        let end = find_closing(
+            lines,
+            code_end,
+            |line| line.contains("[[[end]]]"),
+            "cog block",
+        )?;
+
+        let mut attributes = tool_attributes(ConvertFrom::Cog);
+        if let Some((_, checksum)) = lines[end].split_once("(checksum:") {
+            let checksum = checksum.split(')').next().unwrap_or("").trim();
+            attributes.insert("checksum".to_string(), checksum.to_string());
+        }
+
+        // Keep the generator code as a comment and wrap only its output
+        regions.push(Region {
+            id: ids.allocate_numbered("cog"),
+            attributes,
+            begin_line: None,
+            body_start: code_end + 1,
+            end_line: Some(end),
+            body_end: end.saturating_sub(1),
+            indent: indent_of(lines[end]),
+        });
+        idx = end + 1;
+    }
+    Ok(regions)
+}
+
+fn embedme_regions(lines: &[&str], ids: &mut IdAllocator) -> Vec<Region> {
+    let mut regions = Vec::new();
+    let mut idx = 0;
+    while idx + 1 < lines.len() {
+        let trimmed = lines[idx].trim_start();
+        let fence = trimmed.chars().take_while(|c| *c == '`').count();
+        let source = (fence >= 3)
+            .then(|| embedme_source(lines[idx + 1]))
+            .flatten();
+        let close = "`".repeat(fence);
+        let end = source
+            .and_then(|_| (idx + 2..lines.len()).find(|&end| lines[end].trim() == close.as_str()));
+
+        let (Some(source), Some(end)) = (source, end) else {
+            idx += 1;
+            continue;
+        };
+
+        let mut attributes = tool_attributes(ConvertFrom::Embedme);
+        attributes.insert("src".to_string(), source.to_string());
+        let lang = trimmed[fence..].trim();
+        if !lang.is_empty() {
+            attributes.insert("fence".to_string(), lang.to_string());
+        }
+
+        regions.push(Region {
+            id: ids.allocate_numbered("embedme"),
+            attributes,
+            begin_line: None,
+            body_start: idx,
+            end_line: None,
+            body_end: end,
+            indent: indent_of(lines[idx]),
+        });
+        idx = end + 1;
+    }
+    regions
+}
+
+/// The path in an embedme comment such as `// src/lib.rs#L10-L20`.
+fn embedme_source(line: &str) -> Option<&str> {
+    let trimmed = line.trim();
+    let path = ["//", "#", "--", ";", "%", "<!--", "/*"]
+        .iter()
+        .find_map(|comment| trimmed.strip_prefix(comment))?;
+    let path = path.trim_end_matches("-->").trim_end_matches("*/").trim();
+
+    let file = path.split('#').next()?;
+    let looks_like_path = !path.contains(char::is_whitespace)
+        && file
+            .rsplit('/')
+            .next()
+            .is_some_and(|name| name.contains('.') && !name.starts_with('.'));
+    looks_like_path.then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_markdown_magic() {
+        let text = r#"# Docs
+
+<!-- AUTO-GENERATED-CONTENT:START (CODE:src=./src/lib.rs&lines=1-5) -->
+fn main() {}
+<!-- AUTO-GENERATED-CONTENT:END -->
+
+<!-- AUTO-GENERATED-CONTENT:START (TOC) -->
+- [Docs](#docs)
+<!-- AUTO-GENERATED-CONTENT:END -->
+"#;
+
+        let result = convert(text, ConvertFrom::MarkdownMagic, &MarkerSyntax::default()).unwrap();
+
+        assert_eq!(
+            result,
+            r#"# Docs
+
+<!-- KUGIRI-BEGIN: code lines=1-5 src=./src/lib.rs tool=markdown-magic transform=CODE -->
+fn main() {}
+<!-- KUGIRI-END: code -->
+
+<!-- KUGIRI-BEGIN: toc tool=markdown-magic transform=TOC -->
+- [Docs](#docs)
+<!-- KUGIRI-END: toc -->
+"#
+        );
+        assert_eq!(crate::extract(&result, "toc").unwrap(), "- [Docs](#docs)");
+    }
+
+    #[test]
+    fn test_convert_mdsh() {
+        let text = "`$ kugiri --help`\n\n<!-- BEGIN mdsh -->\n```\nUsage\n```\n<!-- END mdsh -->";
+
+        let result = convert(text, ConvertFrom::Mdsh, &MarkerSyntax::default()).unwrap();
+
+        assert_eq!(
+            result,
+            "`$ kugiri --help`\n\n<!-- KUGIRI-BEGIN: mdsh-1 cmd=\"kugiri --help\" fence=text tool=mdsh -->\n```\nUsage\n```\n<!-- KUGIRI-END: mdsh-1 -->"
+        );
+    }
+
+    #[test]
+    fn test_convert_cog() {
+        let text = r#"# [[[cog
+# import cog
+# cog.outl("VERSION = 1")
+# ]]]
+VERSION = 1
+# [[[end]]] (checksum: abc123)
+"#;
+        let syntax = MarkerSyntax::for_path("version.py");
+
+        let result = convert(text, ConvertFrom::Cog, &syntax).unwrap();
+
+        assert_eq!(
+            result,
+            r#"# [[[cog
+# import cog
+# cog.outl("VERSION = 1")
+# ]]]
+# KUGIRI-BEGIN: cog-1 checksum=abc123 tool=cog
+VERSION = 1
+# KUGIRI-END: cog-1
+"#
+        );
+    }
+
+    #[test]
+    fn test_convert_embedme() {
+        let text = "Example:\n\n```rust\n// examples/basic.rs#L1-L3\nfn main() {}\n```\n\n```rust\nno source\n```";
+
+        let result = convert(text, ConvertFrom::Embedme, &MarkerSyntax::default()).unwrap();
+
+        assert_eq!(
+            result,
+            "Example:\n\n<!-- KUGIRI-BEGIN: embedme-1 fence=rust src=examples/basic.rs#L1-L3 tool=embedme -->\n```rust\n// examples/basic.rs#L1-L3\nfn main() {}\n```\n<!-- KUGIRI-END: embedme-1 -->\n\n```rust\nno source\n```"
+        );
+    }
+
+    #[test]
+    fn test_convert_avoids_existing_ids() {
+        let text = "<!-- KUGIRI-BEGIN: toc -->\n<!-- KUGIRI-END: toc -->\n<!-- AUTO-GENERATED-CONTENT:START (TOC) -->\n<!-- AUTO-GENERATED-CONTENT:END -->";
+
+        let result = convert(text, ConvertFrom::MarkdownMagic, &MarkerSyntax::default()).unwrap();
+
+        assert!(result.contains("<!-- KUGIRI-BEGIN: toc-2 tool=markdown-magic transform=TOC -->"));
+        assert!(result.ends_with("<!-- KUGIRI-END: toc-2 -->"));
+    }
+
+    #[test]
+    fn test_convert_unclosed_region() {
+        let text = "a\n<!-- BEGIN mdsh -->\nout";
+
+        let result = convert(text, ConvertFrom::Mdsh, &MarkerSyntax::default());
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("mdsh block opened at line 2 is never closed"));
+    }
+
+    #[test]
+    fn test_convert_from_str() {
+        assert_eq!(
+            "markdown-magic".parse::<ConvertFrom>().unwrap(),
+            ConvertFrom::MarkdownMagic
+        );
+        assert!("docz".parse::<ConvertFrom>().is_err());
+    }
+}
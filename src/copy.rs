@@ -0,0 +1,140 @@
+use crate::document::Document;
+use crate::error::{KugiriError, Result};
+use crate::insert::insert_document;
+use crate::update::update_document;
+use std::fmt;
+use std::str::FromStr;
+
+/// A section of a file, written `path#id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionRef {
+    pub path: String,
+    pub id: String,
+}
+
+impl SectionRef {
+    pub fn parse(text: &str) -> Result<Self> {
+        match text.rsplit_once('#') {
+            Some((path, id)) if !path.is_empty() && !id.is_empty() => Ok(SectionRef {
+                path: path.to_string(),
+                id: id.to_string(),
+            }),
+            _ => Err(KugiriError::invalid(format!(
+                "Invalid section reference '{text}': expected 'path#id'"
+            ))),
+        }
+    }
+}
+
+impl FromStr for SectionRef {
+    type Err = KugiriError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SectionRef::parse(s)
+    }
+}
+
+impl fmt::Display for SectionRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}#{}", self.path, self.id)
+    }
+}
+
+/// Copy the content of section `from_id` of `from` into section `to_id` of
+/// `to`, which may be the same document. The content is re-indented from the
+/// source section's indent to the target's.
+///
+/// A missing target is created next to the `before` or `after` anchor when
+/// one is given, and is an error otherwise.
+pub fn copy_section(
+    from: &Document,
+    from_id: &str,
+    to: &Document,
+    to_id: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> Result<String> {
+    // Section content is stored without the section's indent
+    let content = from.require(from_id)?.section.content.clone();
+
+    // A target with broken markers is neither updated nor created again
+    to.check_markers(to_id)?;
+    match to.get(to_id) {
+        Some(target) => update_document(to, &[target], &content),
+        None if before.is_some() || after.is_some() => {
+            insert_document(to, to_id, &content, before, after, false)
+        }
+        None => Err(KugiriError::NoMatch(format!(
+            "Section with id '{to_id}' not found; pass --create-before or --create-after to create it"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markers::MarkerSyntax;
+
+    #[test]
+    fn test_parse_section_ref() {
+        let reference = SectionRef::parse("docs/cli.md#usage").unwrap();
+        assert_eq!(reference.path, "docs/cli.md");
+        assert_eq!(reference.id, "usage");
+        assert_eq!(reference.to_string(), "docs/cli.md#usage");
+
+        assert!(SectionRef::parse("docs/cli.md").is_err());
+        assert!(SectionRef::parse("#usage").is_err());
+        assert!(SectionRef::parse("docs/cli.md#").is_err());
+    }
+
+    #[test]
+    fn test_copy_reindents() {
+        let syntax = MarkerSyntax::default();
+        let from = Document::parse(
+            "- item\n  <!-- KUGIRI-BEGIN: usage -->\n  run it\n    --flag\n  <!-- KUGIRI-END: usage -->\n",
+            &syntax,
+        );
+        let to = Document::parse(
+            "<!-- KUGIRI-BEGIN: usage -->\nold\n<!-- KUGIRI-END: usage -->\n",
+            &syntax,
+        );
+
+        assert_eq!(
+            copy_section(&from, "usage", &to, "usage", None, None).unwrap(),
+            "<!-- KUGIRI-BEGIN: usage -->\nrun it\n  --flag\n<!-- KUGIRI-END: usage -->\n"
+        );
+    }
+
+    #[test]
+    fn test_copy_creates_missing_target() {
+        let syntax = MarkerSyntax::default();
+        let doc = Document::parse(
+            "<!-- KUGIRI-BEGIN: a -->\ntext\n<!-- KUGIRI-END: a -->\n",
+            &syntax,
+        );
+
+        let err = copy_section(&doc, "a", &doc, "b", None, None).unwrap_err();
+        assert_eq!(err.exit_code(), KugiriError::EXIT_NOT_FOUND);
+
+        assert_eq!(
+            copy_section(&doc, "a", &doc, "b", None, Some("a")).unwrap(),
+            "<!-- KUGIRI-BEGIN: a -->\ntext\n<!-- KUGIRI-END: a -->\n\n<!-- KUGIRI-BEGIN: b -->\ntext\n<!-- KUGIRI-END: b -->\n"
+        );
+    }
+
+    #[test]
+    fn test_copy_refuses_broken_target() {
+        let syntax = MarkerSyntax::default();
+        let from = Document::parse(
+            "<!-- KUGIRI-BEGIN: a -->\ntext\n<!-- KUGIRI-END: a -->\n",
+            &syntax,
+        );
+        let to = Document::parse(
+            "<!-- KUGIRI-INSERT: top -->\n<!-- KUGIRI-BEGIN: b -->\nold\n",
+            &syntax,
+        );
+
+        let err = copy_section(&from, "a", &to, "b", None, Some("top")).unwrap_err();
+        assert_eq!(err.exit_code(), KugiriError::EXIT_MALFORMED);
+    }
+}
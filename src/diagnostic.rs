@@ -0,0 +1,517 @@
+use crate::document::{Document, MarkerProblem, SectionNode};
+use crate::error::KugiriError;
+use crate::markers::MarkerKind;
+use std::fmt::Write as _;
+use std::ops::Range;
+
+/// An error report in the style of a compiler message: the location, the
+/// offending source lines with their markers underlined, and a hint.
+///
+/// ```text
+/// error: BEGIN marker for 'foo' at line 2 and END marker at line 4 are indented differently
+///  --> README.md:4:1
+///   |
+/// 2 |   <!-- KUGIRI-BEGIN: foo -->
+///   |   -------------------------- BEGIN marker indented by 2 spaces
+/// 3 |   text
+/// 4 | <!-- KUGIRI-END: foo -->
+///   | ^^^^^^^^^^^^^^^^^^^^^^^^ END marker not indented
+///   |
+///   = hint: END marker for 'foo' found at line 4 but BEGIN is indented differently
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Short name of the rule that was broken, shown as `error[code]`
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub path: Option<String>,
+    pub labels: Vec<Label>,
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    /// Reported, but doesn't make `check` fail
+    Warning,
+}
+
+/// An underlined span of one source line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// 0-based line number
+    pub line: usize,
+    /// Byte range within the line
+    pub span: Range<usize>,
+    pub message: String,
+    /// The location the diagnostic is about, underlined with `^`
+    pub primary: bool,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            path: None,
+            labels: Vec::new(),
+            hint: None,
+        }
+    }
+
+    /// Describe `err` for the file at `path`, pointing into `doc` where the
+    /// error concerns its markers.
+    pub fn from_error(err: &KugiriError, path: &str, doc: &Document) -> Self {
+        let mut diagnostic = Diagnostic::new(err.to_string());
+        match err {
+            KugiriError::SectionNotFound { id } => {
+                diagnostic.path = Some(path.to_string());
+                diagnostic.suggest_ids(doc, id, doc.sections());
+            }
+            KugiriError::AnchorNotFound { id } => {
+                diagnostic.path = Some(path.to_string());
+                diagnostic.suggest_ids(doc, id, doc.nodes().iter());
+            }
+            KugiriError::Malformed(problem) => {
+                diagnostic.path = Some(path.to_string());
+                diagnostic.describe_problem(doc, problem);
+            }
+            KugiriError::NoMatch(_) | KugiriError::Duplicate(_) => {
+                diagnostic.path = Some(path.to_string());
+            }
+            KugiriError::Lint { .. }
+            | KugiriError::Command(_)
+            | KugiriError::Conflict(_)
+            | KugiriError::Operation { .. }
+            | KugiriError::Drift { .. }
+            | KugiriError::Invalid(_)
+            | KugiriError::Io { .. } => {}
+        }
+        diagnostic
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Underline the marker text of `line`.
+    pub(crate) fn label(&mut self, doc: &Document, line: usize, message: String, primary: bool) {
+        let text = doc.lines()[line];
+        let start = text.len() - text.trim_start().len();
+        self.labels.push(Label {
+            line,
+            span: start..text.trim_end().len().max(start),
+            message,
+            primary,
+        });
+    }
+
+    /// Underline the BEGIN marker of `node`, or both markers of an inline section.
+    pub(crate) fn label_node(
+        &mut self,
+        doc: &Document,
+        node: &SectionNode,
+        message: String,
+        primary: bool,
+    ) {
+        match &node.section.inline {
+            Some(inline) => self.labels.push(Label {
+                line: node.section.start_line,
+                span: inline.outer.clone(),
+                message,
+                primary,
+            }),
+            None => self.label(doc, node.section.start_line, message, primary),
+        }
+    }
+
+    fn suggest_ids<'d>(
+        &mut self,
+        doc: &Document,
+        id: &str,
+        nodes: impl Iterator<Item = &'d SectionNode>,
+    ) {
+        let similar = similar_ids(id, nodes);
+        for node in &similar {
+            self.label_node(doc, node, format!("similar id '{}'", node.id()), false);
+        }
+
+        let mut names: Vec<String> = similar
+            .iter()
+            .map(|node| format!("'{}'", node.id()))
+            .collect();
+        names.dedup();
+        self.hint = match names.as_slice() {
+            [] => None,
+            [name] => Some(format!("did you mean {name}?")),
+            names => Some(format!("did you mean one of {}?", names.join(", "))),
+        };
+    }
+
+    fn describe_problem(&mut self, doc: &Document, problem: &MarkerProblem) {
+        let syntax = doc.syntax();
+        match problem {
+            MarkerProblem::Crossed {
+                first,
+                first_lines,
+                second,
+                second_lines,
+            } => {
+                let message = format!("'{second}' begins inside '{first}'");
+                self.label(doc, second_lines.0, message, false);
+                self.label(doc, first_lines.1, format!("'{first}' ends here"), false);
+                let message = format!("'{second}' ends after '{first}'");
+                self.label(doc, second_lines.1, message, true);
+                self.hint = Some(format!(
+                    "move the END marker for '{second}' above line {} so that it nests inside '{first}'",
+                    first_lines.1 + 1
+                ));
+            }
+            MarkerProblem::OrphanEnd { id, line } => {
+                let message = "END marker without a BEGIN marker".to_string();
+                self.label(doc, *line, message, true);
+                self.hint = Some(match similar_problem(doc, id, *line, MarkerKind::Begin) {
+                    Some((other, begin_line)) => {
+                        let message = format!("BEGIN marker for '{other}' is never closed");
+                        self.label(doc, begin_line, message, false);
+                        format!("did you mean '{other}'?")
+                    }
+                    None => format!(
+                        "add `{}` before the section's content, or remove this marker",
+                        syntax.begin_marker(id)
+                    ),
+                });
+            }
+            MarkerProblem::UnclosedBegin { id, line } => {
+                self.label(doc, *line, "BEGIN marker is never closed".to_string(), true);
+                self.hint = Some(match similar_problem(doc, id, *line, MarkerKind::End) {
+                    Some((other, end_line)) => {
+                        let message = format!("END marker for '{other}' has no BEGIN marker");
+                        self.label(doc, end_line, message, false);
+                        format!(
+                            "the END marker at line {} may be meant to close '{id}'",
+                            end_line + 1
+                        )
+                    }
+                    None => format!(
+                        "add `{}` after the section's content",
+                        syntax.end_marker(id)
+                    ),
+                });
+            }
+            MarkerProblem::IndentMismatch {
+                id,
+                begin_line,
+                end_line,
+            } => {
+                let lines = doc.lines();
+                let message = format!("BEGIN marker {}", describe_indent(lines[*begin_line]));
+                self.label(doc, *begin_line, message, false);
+                let message = format!("END marker {}", describe_indent(lines[*end_line]));
+                self.label(doc, *end_line, message, true);
+                self.hint = Some(format!(
+                    "END marker for '{id}' found at line {} but BEGIN is indented differently",
+                    end_line + 1
+                ));
+            }
+        }
+    }
+
+    /// Render the diagnostic; `lines` are the lines of the file the labels
+    /// point into. With `color`, ANSI escapes highlight it like rustc does.
+    pub fn render(&self, lines: &[&str], color: bool) -> String {
+        let paint = Paint(color);
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}",
+            {
+                let (level, style) = match self.severity {
+                    Severity::Error => ("error", RED),
+                    Severity::Warning => ("warning", YELLOW),
+                };
+                match self.code {
+                    Some(code) => paint.apply(style, &format!("{level}[{code}]")),
+                    None => paint.apply(style, level),
+                }
+            },
+            paint.apply(BOLD, &format!(": {}", self.message))
+        );
+
+        let mut labels: Vec<&Label> = self
+            .labels
+            .iter()
+            .filter(|label| label.line < lines.len())
+            .collect();
+        labels.sort_by_key(|label| label.line);
+
+        let width = labels
+            .last()
+            .map_or(1, |label| (label.line + 1).to_string().len());
+        let gutter = format!("{} |", " ".repeat(width));
+        let gutter = paint.apply(BLUE, &gutter);
+
+        if let Some(path) = &self.path {
+            let location = match self.labels.iter().find(|label| label.primary) {
+                Some(label) if label.line < lines.len() => {
+                    let column = display_width(&lines[label.line][..label.span.start]) + 1;
+                    format!("{path}:{}:{column}", label.line + 1)
+                }
+                _ => path.clone(),
+            };
+            let arrow = paint.apply(BLUE, "-->");
+            let _ = writeln!(out, "{}{arrow} {location}", " ".repeat(width));
+        }
+
+        if !labels.is_empty() {
+            let _ = writeln!(out, "{gutter}");
+            let mut previous: Option<usize> = None;
+            for label in &labels {
+                let line = lines[label.line];
+                if previous != Some(label.line) {
+                    match previous {
+                        // Show a single skipped line, elide longer gaps
+                        Some(prev) if label.line == prev + 2 => {
+                            self.write_line(&mut out, &paint, width, prev + 1, lines[prev + 1]);
+                        }
+                        Some(prev) if label.line > prev + 2 => {
+                            let _ = writeln!(out, "{}", paint.apply(BLUE, "..."));
+                        }
+                        _ => {}
+                    }
+                    self.write_line(&mut out, &paint, width, label.line, line);
+                }
+
+                let offset = display_width(&line[..label.span.start]);
+                let length = display_width(&line[label.span.clone()]).max(1);
+                let (mark, style) = if label.primary {
+                    ('^', RED)
+                } else {
+                    ('-', BLUE)
+                };
+                let underline = format!("{} {}", mark.to_string().repeat(length), label.message);
+                let _ = writeln!(
+                    out,
+                    "{gutter} {}{}",
+                    " ".repeat(offset),
+                    paint.apply(style, &underline)
+                );
+                previous = Some(label.line);
+            }
+        }
+
+        if let Some(hint) = &self.hint {
+            if !labels.is_empty() {
+                let _ = writeln!(out, "{gutter}");
+            }
+            let equals = paint.apply(BLUE, "=");
+            let _ = writeln!(
+                out,
+                "{} {equals} {}: {hint}",
+                " ".repeat(width),
+                paint.apply(BOLD, "hint")
+            );
+        }
+        out
+    }
+
+    fn write_line(&self, out: &mut String, paint: &Paint, width: usize, number: usize, line: &str) {
+        let number = paint.apply(BLUE, &format!("{:>width$} |", number + 1));
+        let _ = writeln!(out, "{number} {}", line.replace('\t', "    ").trim_end());
+    }
+}
+
+/// Sections whose ids look like a misspelling of `id`, closest first.
+pub fn similar_ids<'d>(
+    id: &str,
+    nodes: impl Iterator<Item = &'d SectionNode>,
+) -> Vec<&'d SectionNode> {
+    let mut candidates: Vec<(usize, &SectionNode)> = nodes
+        .filter(|node| node.id() != id)
+        .filter_map(|node| is_similar(id, node.id()).map(|distance| (distance, node)))
+        .collect();
+    // Stable, so equally close ids stay in document order
+    candidates.sort_by_key(|(distance, _)| *distance);
+    candidates.truncate(3);
+    candidates.into_iter().map(|(_, node)| node).collect()
+}
+
+/// The edit distance between two ids if it is small enough to be a typo.
+fn is_similar(a: &str, b: &str) -> Option<usize> {
+    if a.eq_ignore_ascii_case(b) {
+        return Some(0);
+    }
+    let distance = edit_distance(a, b);
+    let limit = (a.chars().count().max(b.chars().count()) / 3).max(1);
+    (distance <= limit).then_some(distance)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// An unclosed BEGIN marker before `line`, or an orphan END marker after
+/// it, whose id is close to `id`: often the other half of a misspelt pair.
+fn similar_problem(
+    doc: &Document,
+    id: &str,
+    line: usize,
+    kind: MarkerKind,
+) -> Option<(String, usize)> {
+    doc.problems().iter().find_map(|problem| {
+        let (other, other_line) = match (problem, kind) {
+            (MarkerProblem::UnclosedBegin { id, line: begin }, MarkerKind::Begin)
+                if *begin < line =>
+            {
+                (id, *begin)
+            }
+            (MarkerProblem::OrphanEnd { id, line: end }, MarkerKind::End) if *end > line => {
+                (id, *end)
+            }
+            _ => return None,
+        };
+        (other != id && is_similar(id, other).is_some()).then(|| (other.clone(), other_line))
+    })
+}
+
+fn describe_indent(line: &str) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let tabs = indent.matches('\t').count();
+    let spaces = indent.len() - tabs;
+    let plural = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+    match (spaces, tabs) {
+        (0, 0) => "not indented".to_string(),
+        (n, 0) => format!("indented by {}", plural(n, "space")),
+        (0, n) => format!("indented by {}", plural(n, "tab")),
+        (s, t) => format!(
+            "indented by {} and {}",
+            plural(s, "space"),
+            plural(t, "tab")
+        ),
+    }
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+pub(crate) const RED: &str = "\x1b[1;31m";
+pub(crate) const GREEN: &str = "\x1b[32m";
+pub(crate) const YELLOW: &str = "\x1b[1;33m";
+pub(crate) const BLUE: &str = "\x1b[1;34m";
+pub(crate) const CYAN: &str = "\x1b[36m";
+pub(crate) const BOLD: &str = "\x1b[1m";
+
+/// Wraps text in ANSI styles when enabled.
+pub(crate) struct Paint(pub(crate) bool);
+
+impl Paint {
+    pub(crate) fn apply(&self, style: &str, text: &str) -> String {
+        if self.0 {
+            format!("{style}{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markers::MarkerSyntax;
+
+    fn diagnose(text: &str, err: KugiriError) -> String {
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        Diagnostic::from_error(&err, "README.md", &doc).render(doc.lines(), false)
+    }
+
+    #[test]
+    fn test_render_indent_mismatch() {
+        let text = "x\n  <!-- KUGIRI-BEGIN: foo -->\n  text\n<!-- KUGIRI-END: foo -->\n";
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        let err = KugiriError::from(doc.problems()[0].clone());
+
+        assert_eq!(
+            diagnose(text, err),
+            "error: BEGIN marker for 'foo' at line 2 and END marker at line 4 are indented differently
+ --> README.md:4:1
+  |
+2 |   <!-- KUGIRI-BEGIN: foo -->
+  |   -------------------------- BEGIN marker indented by 2 spaces
+3 |   text
+4 | <!-- KUGIRI-END: foo -->
+  | ^^^^^^^^^^^^^^^^^^^^^^^^ END marker not indented
+  |
+  = hint: END marker for 'foo' found at line 4 but BEGIN is indented differently
+"
+        );
+    }
+
+    #[test]
+    fn test_render_near_miss_ids() {
+        let text = "<!-- KUGIRI-BEGIN: install -->\n<!-- KUGIRI-END: install -->\n\n\nv<!-- KUGIRI-BEGIN: Instal -->1<!-- KUGIRI-END: Instal -->";
+        let err = KugiriError::SectionNotFound {
+            id: "instal".to_string(),
+        };
+
+        assert_eq!(
+            diagnose(text, err),
+            "error: Section with id 'instal' not found
+ --> README.md
+  |
+1 | <!-- KUGIRI-BEGIN: install -->
+  | ------------------------------ similar id 'install'
+...
+5 | v<!-- KUGIRI-BEGIN: Instal -->1<!-- KUGIRI-END: Instal -->
+  |  --------------------------------------------------------- similar id 'Instal'
+  |
+  = hint: did you mean one of 'Instal', 'install'?
+"
+        );
+
+        let err = KugiriError::SectionNotFound {
+            id: "changelog".to_string(),
+        };
+        assert_eq!(
+            diagnose(text, err),
+            "error: Section with id 'changelog' not found\n --> README.md\n"
+        );
+    }
+
+    #[test]
+    fn test_render_without_source() {
+        let diagnostic = Diagnostic::new("Selector must not be empty");
+        assert_eq!(
+            diagnostic.render(&[], false),
+            "error: Selector must not be empty\n"
+        );
+        assert_eq!(
+            diagnostic.render(&[], true),
+            "\x1b[1;31merror\x1b[0m\x1b[1m: Selector must not be empty\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_similar_ids() {
+        assert_eq!(edit_distance("instal", "install"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(is_similar("README", "readme"), Some(0));
+        assert_eq!(is_similar("v1.0", "v1.1"), Some(1));
+        assert_eq!(is_similar("a", "xyz"), None);
+    }
+}
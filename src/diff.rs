@@ -0,0 +1,89 @@
+use crate::diagnostic::{Paint, BOLD, CYAN, GREEN, RED};
+use similar::TextDiff;
+
+/// A unified diff from `old` to `new` with `a/` and `b/` headers for `path`,
+/// empty when the texts are identical. The result can be applied with
+/// `git apply` when `path` is relative to the repository root.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    let path = path.trim_start_matches("./");
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{path}"), &format!("b/{path}"))
+        .to_string()
+}
+
+/// Color a unified diff for a terminal like `git diff` does.
+pub fn colorize_diff(diff: &str) -> String {
+    let paint = Paint(true);
+    let mut out = String::with_capacity(diff.len());
+    for line in diff.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        let style = if text.starts_with("---") || text.starts_with("+++") {
+            Some(BOLD)
+        } else if text.starts_with("@@") {
+            Some(CYAN)
+        } else if text.starts_with('-') {
+            Some(RED)
+        } else if text.starts_with('+') {
+            Some(GREEN)
+        } else {
+            None
+        };
+        match style {
+            Some(style) => out.push_str(&paint.apply(style, text)),
+            None => out.push_str(text),
+        }
+        out.push_str(newline);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "# Title\n<!-- KUGIRI-BEGIN: a -->\nold\n<!-- KUGIRI-END: a -->\n";
+        let new = "# Title\n<!-- KUGIRI-BEGIN: a -->\nnew\n<!-- KUGIRI-END: a -->\n";
+
+        assert_eq!(
+            unified_diff("README.md", old, new),
+            "--- a/README.md
++++ b/README.md
+@@ -1,4 +1,4 @@
+ # Title
+ <!-- KUGIRI-BEGIN: a -->
+-old
++new
+ <!-- KUGIRI-END: a -->
+"
+        );
+        assert_eq!(unified_diff("README.md", old, old), "");
+        assert!(unified_diff("./README.md", old, new).starts_with("--- a/README.md\n"));
+    }
+
+    #[test]
+    fn test_unified_diff_missing_newline() {
+        assert_eq!(
+            unified_diff("a.txt", "old", "new"),
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-old\n\\ No newline at end of file\n+new\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_colorize_diff() {
+        let diff = "--- a/x\n+++ b/x\n@@ -1 +1 @@\n-old\n+new\n same\n";
+        assert_eq!(
+            colorize_diff(diff),
+            "\x1b[1m--- a/x\x1b[0m\n\x1b[1m+++ b/x\x1b[0m\n\x1b[36m@@ -1 +1 @@\x1b[0m\n\x1b[1;31m-old\x1b[0m\n\x1b[32m+new\x1b[0m\n same\n"
+        );
+    }
+}
@@ -0,0 +1,764 @@
+use crate::attributes::{parse_attributes, Attributes};
+use crate::error::{KugiriError, Result};
+use crate::markers::{find_inline_sections, InlineSpan, MarkerKind, MarkerSyntax, Section};
+use crate::scanner::{classify_lines, LineContext};
+use std::fmt;
+use std::ops::Range;
+
+/// How a node in a [`Document`] is delimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// BEGIN and END markers on their own lines
+    Block,
+    /// BEGIN and END markers embedded in a single line
+    Inline,
+    /// A lone INSERT marker, used as an anchor
+    Insert,
+}
+
+/// A section (or INSERT marker) in a [`Document`], linked into the section tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionNode {
+    pub section: Section,
+    pub kind: NodeKind,
+    /// Position of this node in [`Document::nodes`]
+    pub index: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Number of enclosing sections
+    pub depth: usize,
+    /// Byte range from the start of the BEGIN marker line to the end of the END marker line
+    pub span: Range<usize>,
+    /// Byte range of the content between the markers
+    pub content_span: Range<usize>,
+}
+
+impl SectionNode {
+    pub fn id(&self) -> &str {
+        &self.section.id
+    }
+}
+
+/// Which of several sections sharing an id a command acts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Occurrence {
+    /// The first section in the file
+    #[default]
+    First,
+    /// The n-th section, counting from 1
+    Nth(usize),
+    /// Every section
+    All,
+}
+
+impl Occurrence {
+    /// Pick from `nodes`, which must not be empty and are in document order.
+    pub fn select<'n>(self, nodes: Vec<&'n SectionNode>, id: &str) -> Result<Vec<&'n SectionNode>> {
+        match self {
+            Occurrence::First => Ok(nodes.into_iter().take(1).collect()),
+            Occurrence::All => Ok(nodes),
+            Occurrence::Nth(n) if n >= 1 && n <= nodes.len() => Ok(vec![nodes[n - 1]]),
+            Occurrence::Nth(n) => Err(KugiriError::NoMatch(format!(
+                "Section with id '{id}' occurs {} time(s); there is no occurrence {n}",
+                nodes.len()
+            ))),
+        }
+    }
+}
+
+/// Drop nodes nested inside other nodes of the list, keeping document order.
+pub fn outermost<'n>(nodes: &[&'n SectionNode]) -> Vec<&'n SectionNode> {
+    let mut sorted = nodes.to_vec();
+    sorted.sort_by_key(|node| node.span.start);
+
+    let mut kept: Vec<&SectionNode> = Vec::with_capacity(sorted.len());
+    for node in sorted {
+        let nested = kept
+            .last()
+            .is_some_and(|last| node.span.end <= last.span.end);
+        if !nested {
+            kept.push(node);
+        }
+    }
+    kept
+}
+
+/// A marker structure error found while parsing a [`Document`].
+///
+/// Line numbers are 0-based like [`Section::start_line`]; messages show them
+/// 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkerProblem {
+    /// `second` begins inside `first` but ends after it
+    Crossed {
+        first: String,
+        first_lines: (usize, usize),
+        second: String,
+        second_lines: (usize, usize),
+    },
+    /// An END marker with no open BEGIN marker for its id
+    OrphanEnd { id: String, line: usize },
+    /// A BEGIN marker that is never closed
+    UnclosedBegin { id: String, line: usize },
+    /// BEGIN and END markers of a section with different indentation
+    IndentMismatch {
+        id: String,
+        begin_line: usize,
+        end_line: usize,
+    },
+}
+
+impl MarkerProblem {
+    /// Whether the problem concerns the section with this id.
+    pub fn involves(&self, id: &str) -> bool {
+        match self {
+            MarkerProblem::Crossed { first, second, .. } => first == id || second == id,
+            MarkerProblem::OrphanEnd { id: problem_id, .. }
+            | MarkerProblem::UnclosedBegin { id: problem_id, .. }
+            | MarkerProblem::IndentMismatch { id: problem_id, .. } => problem_id == id,
+        }
+    }
+
+    /// The first line the problem concerns.
+    pub fn line(&self) -> usize {
+        match self {
+            MarkerProblem::Crossed { first_lines, .. } => first_lines.0,
+            MarkerProblem::OrphanEnd { line, .. } | MarkerProblem::UnclosedBegin { line, .. } => {
+                *line
+            }
+            MarkerProblem::IndentMismatch { begin_line, .. } => *begin_line,
+        }
+    }
+}
+
+impl fmt::Display for MarkerProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkerProblem::Crossed {
+                first,
+                first_lines,
+                second,
+                second_lines,
+            } => write!(
+                f,
+                "Sections '{first}' (lines {}-{}) and '{second}' (lines {}-{}) cross: \
+                 '{second}' begins inside '{first}' but ends after it",
+                first_lines.0 + 1,
+                first_lines.1 + 1,
+                second_lines.0 + 1,
+                second_lines.1 + 1
+            ),
+            MarkerProblem::OrphanEnd { id, line } => write!(
+                f,
+                "END marker for '{id}' at line {} has no matching BEGIN marker",
+                line + 1
+            ),
+            MarkerProblem::UnclosedBegin { id, line } => write!(
+                f,
+                "Found begin marker for '{id}' at line {} but not end marker; file may be corrupt",
+                line + 1
+            ),
+            MarkerProblem::IndentMismatch {
+                id,
+                begin_line,
+                end_line,
+            } => write!(
+                f,
+                "BEGIN marker for '{id}' at line {} and END marker at line {} are indented differently",
+                begin_line + 1,
+                end_line + 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MarkerProblem {}
+
+/// A file parsed once into its sections.
+///
+/// Sections are kept in the order their BEGIN markers appear and linked into
+/// a tree by nesting. Unbalanced or crossed markers are left out of the tree
+/// and reported by [`Document::problems`].
+#[derive(Debug, Clone)]
+pub struct Document<'a> {
+    text: &'a str,
+    syntax: MarkerSyntax,
+    lines: Vec<&'a str>,
+    contexts: Vec<LineContext>,
+    marker_lines: Vec<bool>,
+    nodes: Vec<SectionNode>,
+    problems: Vec<MarkerProblem>,
+}
+
+/// A matched marker pair or INSERT marker, before it is placed in the tree.
+struct Pending<'a> {
+    kind: NodeKind,
+    id: &'a str,
+    attributes: &'a str,
+    start_line: usize,
+    end_line: usize,
+    /// Byte offsets within the line for inline sections
+    inline: Option<InlineSpan>,
+}
+
+impl<'a> Document<'a> {
+    pub fn parse(text: &'a str, syntax: &MarkerSyntax) -> Self {
+        let lines: Vec<&str> = text.lines().collect();
+        let contexts = classify_lines(&lines, syntax);
+        let mut marker_lines = vec![false; lines.len()];
+
+        let mut pending: Vec<Pending> = Vec::new();
+        let mut problems = Vec::new();
+        let mut open: Vec<Pending> = Vec::new();
+        // Sections still open when an enclosing section ended, with that
+        // section's id and lines: they either cross it or are never closed
+        let mut interrupted: Vec<(Pending, &str, (usize, usize))> = Vec::new();
+
+        for (idx, &line) in lines.iter().enumerate() {
+            if contexts[idx] != LineContext::Active {
+                continue;
+            }
+            let Some(marker) = syntax.parse_marker(line) else {
+                for (marker, span) in find_inline_sections(line, syntax) {
+                    pending.push(Pending {
+                        kind: NodeKind::Inline,
+                        id: marker.id,
+                        attributes: marker.attributes,
+                        start_line: idx,
+                        end_line: idx,
+                        inline: Some(span),
+                    });
+                }
+                continue;
+            };
+            marker_lines[idx] = true;
+
+            match marker.kind {
+                MarkerKind::Begin => open.push(Pending {
+                    kind: NodeKind::Block,
+                    id: marker.id,
+                    attributes: marker.attributes,
+                    start_line: idx,
+                    end_line: idx,
+                    inline: None,
+                }),
+                MarkerKind::End => {
+                    if let Some(pos) = open.iter().rposition(|begin| begin.id == marker.id) {
+                        let section_lines = (open[pos].start_line, idx);
+                        for inner in open.drain(pos + 1..) {
+                            interrupted.push((inner, marker.id, section_lines));
+                        }
+                        let mut begin = open.pop().expect("position is in range");
+                        begin.end_line = idx;
+                        if indent_of(lines[begin.start_line]) != indent_of(line) {
+                            problems.push(MarkerProblem::IndentMismatch {
+                                id: marker.id.to_string(),
+                                begin_line: begin.start_line,
+                                end_line: idx,
+                            });
+                        }
+                        pending.push(begin);
+                    } else if let Some(pos) = interrupted
+                        .iter()
+                        .position(|(inner, _, _)| inner.id == marker.id)
+                    {
+                        let (inner, first, first_lines) = interrupted.remove(pos);
+                        problems.push(MarkerProblem::Crossed {
+                            first: first.to_string(),
+                            first_lines,
+                            second: inner.id.to_string(),
+                            second_lines: (inner.start_line, idx),
+                        });
+                    } else {
+                        problems.push(MarkerProblem::OrphanEnd {
+                            id: marker.id.to_string(),
+                            line: idx,
+                        });
+                    }
+                }
+                MarkerKind::Insert => pending.push(Pending {
+                    kind: NodeKind::Insert,
+                    id: marker.id,
+                    attributes: marker.attributes,
+                    start_line: idx,
+                    end_line: idx,
+                    inline: None,
+                }),
+            }
+        }
+
+        let unclosed = open
+            .into_iter()
+            .chain(interrupted.into_iter().map(|(inner, _, _)| inner));
+        for begin in unclosed {
+            problems.push(MarkerProblem::UnclosedBegin {
+                id: begin.id.to_string(),
+                line: begin.start_line,
+            });
+        }
+        problems.sort_by_key(MarkerProblem::line);
+
+        pending.sort_by_key(|p| {
+            (
+                p.start_line,
+                p.inline.as_ref().map_or(0, |span| span.outer.start),
+            )
+        });
+
+        let mut document = Document {
+            text,
+            syntax: syntax.clone(),
+            lines,
+            contexts,
+            marker_lines,
+            nodes: Vec::with_capacity(pending.len()),
+            problems,
+        };
+        let line_starts = line_starts(text);
+
+        let mut stack: Vec<usize> = Vec::new();
+        for p in pending {
+            while let Some(&top) = stack.last() {
+                let parent = &document.nodes[top].section;
+                if parent.start_line < p.start_line && p.end_line < parent.end_line {
+                    break;
+                }
+                stack.pop();
+            }
+
+            let index = document.nodes.len();
+            let parent = stack.last().copied();
+            if let Some(parent) = parent {
+                document.nodes[parent].children.push(index);
+            }
+            let node = document.node_for(p, index, parent, stack.len(), &line_starts);
+            if node.kind == NodeKind::Block {
+                stack.push(index);
+            }
+            document.nodes.push(node);
+        }
+
+        document
+    }
+
+    fn node_for(
+        &self,
+        p: Pending,
+        index: usize,
+        parent: Option<usize>,
+        depth: usize,
+        line_starts: &[usize],
+    ) -> SectionNode {
+        let line = self.lines[p.start_line];
+        let indent = indent_of(line);
+        let line_start = line_starts[p.start_line];
+        let end_line_end = line_starts[p.end_line] + self.lines[p.end_line].len();
+
+        let (content, span, content_span) = match (&p.inline, p.kind) {
+            (Some(inline), _) => (
+                line[inline.inner.clone()].to_string(),
+                line_start + inline.outer.start..line_start + inline.outer.end,
+                line_start + inline.inner.start..line_start + inline.inner.end,
+            ),
+            (None, NodeKind::Block) => {
+                // Remove the section indent from content lines
+                let content = self.lines[p.start_line + 1..p.end_line]
+                    .iter()
+                    .map(|line| line.strip_prefix(indent).unwrap_or(line))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                (
+                    content,
+                    line_start..end_line_end,
+                    line_starts[p.start_line + 1]..line_starts[p.end_line],
+                )
+            }
+            (None, _) => (
+                String::new(),
+                line_start..end_line_end,
+                end_line_end..end_line_end,
+            ),
+        };
+
+        let attributes = if p.kind == NodeKind::Insert {
+            Attributes::new()
+        } else {
+            parse_attributes(p.attributes)
+        };
+
+        SectionNode {
+            section: Section {
+                id: p.id.to_string(),
+                start_line: p.start_line,
+                end_line: p.end_line,
+                content,
+                indent: indent.to_string(),
+                inline: p.inline,
+                attributes,
+            },
+            kind: p.kind,
+            index,
+            parent,
+            children: Vec::new(),
+            depth,
+            span,
+            content_span,
+        }
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn syntax(&self) -> &MarkerSyntax {
+        &self.syntax
+    }
+
+    pub fn lines(&self) -> &[&'a str] {
+        &self.lines
+    }
+
+    pub fn line_context(&self, line: usize) -> LineContext {
+        self.contexts[line]
+    }
+
+    /// Whether the line is a recognised BEGIN, END or INSERT marker.
+    pub fn is_marker_line(&self, line: usize) -> bool {
+        self.marker_lines[line]
+    }
+
+    /// All sections and INSERT markers in document order.
+    pub fn nodes(&self) -> &[SectionNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, index: usize) -> &SectionNode {
+        &self.nodes[index]
+    }
+
+    /// Block and inline sections in the order their BEGIN markers appear.
+    pub fn sections(&self) -> impl Iterator<Item = &SectionNode> {
+        self.nodes
+            .iter()
+            .filter(|node| node.kind != NodeKind::Insert)
+    }
+
+    /// Sections that are not nested in another section.
+    pub fn roots(&self) -> impl Iterator<Item = &SectionNode> {
+        self.sections().filter(|node| node.parent.is_none())
+    }
+
+    /// The first section with this id.
+    pub fn get(&self, id: &str) -> Option<&SectionNode> {
+        self.sections().find(|node| node.id() == id)
+    }
+
+    /// The sections directly nested in the first section with this id.
+    pub fn children(&self, id: &str) -> Vec<&SectionNode> {
+        self.get(id)
+            .map(|node| {
+                node.children
+                    .iter()
+                    .map(|&child| &self.nodes[child])
+                    .filter(|child| child.kind != NodeKind::Insert)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn parent(&self, node: &SectionNode) -> Option<&SectionNode> {
+        node.parent.map(|parent| &self.nodes[parent])
+    }
+
+    /// Marker structure errors, ordered by line.
+    pub fn problems(&self) -> &[MarkerProblem] {
+        &self.problems
+    }
+
+    /// The first section with this id, or an error explaining why it cannot
+    /// be used: a marker problem involving the id, or that it was not found.
+    pub fn require(&self, id: &str) -> Result<&SectionNode> {
+        self.check_markers(id)?;
+        match self.get(id) {
+            Some(node) => Ok(node),
+            None => Err(KugiriError::SectionNotFound { id: id.to_string() }),
+        }
+    }
+
+    /// The sections with this id picked by `occurrence`, with the same errors
+    /// as [`Document::require`].
+    pub fn require_occurrences(
+        &self,
+        id: &str,
+        occurrence: Occurrence,
+    ) -> Result<Vec<&SectionNode>> {
+        self.check_markers(id)?;
+        let nodes: Vec<&SectionNode> = self.sections().filter(|node| node.id() == id).collect();
+        if nodes.is_empty() {
+            return Err(KugiriError::SectionNotFound { id: id.to_string() });
+        }
+        occurrence.select(nodes, id)
+    }
+
+    /// Like [`Document::require`], but also accepts an INSERT marker.
+    pub fn require_anchor(&self, id: &str) -> Result<&SectionNode> {
+        self.check_markers(id)?;
+        match self.anchor(id) {
+            Some(node) => Ok(node),
+            None => Err(KugiriError::AnchorNotFound { id: id.to_string() }),
+        }
+    }
+
+    pub(crate) fn check_markers(&self, id: &str) -> Result<()> {
+        match self.problems.iter().find(|problem| problem.involves(id)) {
+            Some(problem) => Err(problem.clone().into()),
+            None => Ok(()),
+        }
+    }
+
+    /// The section with this id, or else the first INSERT marker with it.
+    pub fn anchor(&self, id: &str) -> Option<&SectionNode> {
+        self.get(id).or_else(|| {
+            self.nodes
+                .iter()
+                .find(|node| node.kind == NodeKind::Insert && node.id() == id)
+        })
+    }
+}
+
+fn indent_of(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Byte offset of the start of each line, plus one past the end of the text.
+fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(text.match_indices('\n').map(|(pos, _)| pos + 1));
+    if !text.ends_with('\n') {
+        starts.push(text.len());
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NESTED: &str = r#"# Doc
+<!-- KUGIRI-INSERT: top -->
+<!-- KUGIRI-BEGIN: outer kind=guide -->
+Intro <!-- KUGIRI-BEGIN: ver -->1.0<!-- KUGIRI-END: ver -->
+  <!-- KUGIRI-BEGIN: inner -->
+  inner text
+  <!-- KUGIRI-END: inner -->
+<!-- KUGIRI-END: outer -->
+<!-- KUGIRI-BEGIN: tail -->
+<!-- KUGIRI-END: tail -->
+"#;
+
+    #[test]
+    fn test_section_tree() {
+        let doc = Document::parse(NESTED, &MarkerSyntax::default());
+
+        let ids: Vec<&str> = doc.sections().map(|node| node.id()).collect();
+        assert_eq!(ids, vec!["outer", "ver", "inner", "tail"]);
+
+        let roots: Vec<&str> = doc.roots().map(|node| node.id()).collect();
+        assert_eq!(roots, vec!["outer", "tail"]);
+
+        let children: Vec<&str> = doc.children("outer").iter().map(|n| n.id()).collect();
+        assert_eq!(children, vec!["ver", "inner"]);
+        assert!(doc.children("inner").is_empty());
+        assert!(doc.children("missing").is_empty());
+
+        let inner = doc.get("inner").unwrap();
+        assert_eq!(inner.kind, NodeKind::Block);
+        assert_eq!(inner.depth, 1);
+        assert_eq!(doc.parent(inner).unwrap().id(), "outer");
+        assert_eq!(inner.section.content, "inner text");
+        assert_eq!(inner.section.indent, "  ");
+
+        let ver = doc.get("ver").unwrap();
+        assert_eq!(ver.kind, NodeKind::Inline);
+        assert_eq!(ver.section.start_line, 3);
+
+        let outer = doc.get("outer").unwrap();
+        assert_eq!(outer.depth, 0);
+        assert_eq!(outer.section.attributes["kind"], "guide");
+    }
+
+    #[test]
+    fn test_byte_spans() {
+        let doc = Document::parse(NESTED, &MarkerSyntax::default());
+
+        let inner = doc.get("inner").unwrap();
+        assert_eq!(
+            &NESTED[inner.span.clone()],
+            "  <!-- KUGIRI-BEGIN: inner -->\n  inner text\n  <!-- KUGIRI-END: inner -->"
+        );
+        assert_eq!(&NESTED[inner.content_span.clone()], "  inner text\n");
+
+        let ver = doc.get("ver").unwrap();
+        assert_eq!(&NESTED[ver.content_span.clone()], "1.0");
+
+        let tail = doc.get("tail").unwrap();
+        assert!(tail.content_span.is_empty());
+    }
+
+    #[test]
+    fn test_insert_markers_are_anchors_not_sections() {
+        let doc = Document::parse(NESTED, &MarkerSyntax::default());
+
+        assert!(doc.get("top").is_none());
+        let top = doc.anchor("top").unwrap();
+        assert_eq!(top.kind, NodeKind::Insert);
+        assert_eq!(top.section.start_line, 1);
+        assert!(doc.is_marker_line(1));
+        assert!(!doc.is_marker_line(0));
+    }
+
+    #[test]
+    fn test_unbalanced_markers_are_skipped() {
+        let text = "<!-- KUGIRI-BEGIN: a -->\n<!-- KUGIRI-BEGIN: open -->\n<!-- KUGIRI-END: a -->\n<!-- KUGIRI-END: stray -->";
+        let doc = Document::parse(text, &MarkerSyntax::default());
+
+        let ids: Vec<&str> = doc.sections().map(|node| node.id()).collect();
+        assert_eq!(ids, vec!["a"]);
+        assert!(doc.children("a").is_empty());
+    }
+
+    fn problems(text: &str) -> Vec<MarkerProblem> {
+        Document::parse(text, &MarkerSyntax::default())
+            .problems()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_crossed_sections() {
+        let text = "<!-- KUGIRI-BEGIN: a -->\n<!-- KUGIRI-BEGIN: b -->\n<!-- KUGIRI-END: a -->\n<!-- KUGIRI-END: b -->";
+        let found = problems(text);
+        assert_eq!(
+            found,
+            vec![MarkerProblem::Crossed {
+                first: "a".to_string(),
+                first_lines: (0, 2),
+                second: "b".to_string(),
+                second_lines: (1, 3),
+            }]
+        );
+        assert_eq!(
+            found[0].to_string(),
+            "Sections 'a' (lines 1-3) and 'b' (lines 2-4) cross: 'b' begins inside 'a' but ends after it"
+        );
+    }
+
+    #[test]
+    fn test_orphan_and_unclosed_markers() {
+        let text = "<!-- KUGIRI-BEGIN: open -->\n<!-- KUGIRI-BEGIN: a -->\n<!-- KUGIRI-END: a -->\n<!-- KUGIRI-END: stray -->";
+        let found = problems(text);
+        assert_eq!(
+            found,
+            vec![
+                MarkerProblem::UnclosedBegin {
+                    id: "open".to_string(),
+                    line: 0
+                },
+                MarkerProblem::OrphanEnd {
+                    id: "stray".to_string(),
+                    line: 3
+                },
+            ]
+        );
+        assert_eq!(
+            found[0].to_string(),
+            "Found begin marker for 'open' at line 1 but not end marker; file may be corrupt"
+        );
+        assert_eq!(
+            found[1].to_string(),
+            "END marker for 'stray' at line 4 has no matching BEGIN marker"
+        );
+    }
+
+    #[test]
+    fn test_inner_section_never_closed() {
+        let text = "<!-- KUGIRI-BEGIN: outer -->\n<!-- KUGIRI-BEGIN: inner -->\n<!-- KUGIRI-END: outer -->";
+        assert_eq!(
+            problems(text),
+            vec![MarkerProblem::UnclosedBegin {
+                id: "inner".to_string(),
+                line: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_indent_mismatch() {
+        let text = "  <!-- KUGIRI-BEGIN: a -->\n  body\n<!-- KUGIRI-END: a -->";
+        let found = problems(text);
+        assert_eq!(
+            found[0].to_string(),
+            "BEGIN marker for 'a' at line 1 and END marker at line 3 are indented differently"
+        );
+        assert!(problems("<!-- KUGIRI-BEGIN: a -->\n<!-- KUGIRI-END: a -->").is_empty());
+    }
+
+    #[test]
+    fn test_require() {
+        let text = "<!-- KUGIRI-BEGIN: ok -->\n<!-- KUGIRI-END: ok -->\n<!-- KUGIRI-END: stray -->\n<!-- KUGIRI-INSERT: top -->";
+        let doc = Document::parse(text, &MarkerSyntax::default());
+
+        assert_eq!(doc.require("ok").unwrap().id(), "ok");
+        let err = doc.require("stray").unwrap_err();
+        assert!(err.to_string().contains("line 3 has no matching BEGIN"));
+        let err = doc.require("missing").unwrap_err();
+        assert_eq!(err.to_string(), "Section with id 'missing' not found");
+
+        assert!(doc.require("top").is_err());
+        assert_eq!(doc.require_anchor("top").unwrap().kind, NodeKind::Insert);
+    }
+
+    #[test]
+    fn test_require_occurrences() {
+        let text = "<!-- KUGIRI-BEGIN: v1 -->\na\n<!-- KUGIRI-END: v1 -->\n<!-- KUGIRI-BEGIN: v1 -->\nb\n<!-- KUGIRI-END: v1 -->";
+        let doc = Document::parse(text, &MarkerSyntax::default());
+
+        let content = |occurrence| -> Vec<String> {
+            doc.require_occurrences("v1", occurrence)
+                .unwrap()
+                .iter()
+                .map(|node| node.section.content.clone())
+                .collect()
+        };
+        assert_eq!(content(Occurrence::First), vec!["a"]);
+        assert_eq!(content(Occurrence::Nth(2)), vec!["b"]);
+        assert_eq!(content(Occurrence::All), vec!["a", "b"]);
+
+        let err = doc
+            .require_occurrences("v1", Occurrence::Nth(3))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Section with id 'v1' occurs 2 time(s); there is no occurrence 3"
+        );
+        assert!(doc.require_occurrences("v2", Occurrence::All).is_err());
+    }
+
+    #[test]
+    fn test_outermost() {
+        let doc = Document::parse(NESTED, &MarkerSyntax::default());
+        let nodes: Vec<&SectionNode> = doc.sections().collect();
+        let ids: Vec<&str> = outermost(&nodes).iter().map(|node| node.id()).collect();
+        assert_eq!(ids, vec!["outer", "tail"]);
+    }
+
+    #[test]
+    fn test_crlf_spans() {
+        let text = "x\r\n<!-- KUGIRI-BEGIN: a -->\r\nbody\r\n<!-- KUGIRI-END: a -->\r\n";
+        let doc = Document::parse(text, &MarkerSyntax::default());
+
+        let a = doc.get("a").unwrap();
+        assert_eq!(a.section.content, "body");
+        assert_eq!(&text[a.content_span.clone()], "body\r\n");
+        assert!(text[a.span.clone()].ends_with("<!-- KUGIRI-END: a -->"));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::document::MarkerProblem;
+use std::fmt;
+use std::io;
+
+pub type Result<T, E = KugiriError> = std::result::Result<T, E>;
+
+/// Errors returned by kugiri. Each kind maps to a stable process exit code
+/// (see [`KugiriError::exit_code`]) so that scripts can branch on it.
+#[derive(Debug)]
+pub enum KugiriError {
+    /// No section with the requested id
+    SectionNotFound { id: String },
+    /// No section or INSERT marker to insert relative to
+    AnchorNotFound { id: String },
+    /// A selector, attribute filter or occurrence matched no section
+    NoMatch(String),
+    /// BEGIN/END markers that are crossed, unbalanced or misaligned
+    Malformed(MarkerProblem),
+    /// `check` found marker problems; they were reported as they were found
+    Lint { problems: usize },
+    /// An operation of an `apply` manifest failed
+    Operation {
+        /// 0-based position in the manifest
+        index: usize,
+        path: String,
+        source: Box<KugiriError>,
+    },
+    /// A shell command producing section content failed
+    Command(String),
+    /// `--check` found that a command would change the file
+    Drift { path: String },
+    /// `push` found that the source changed since the last `include`
+    Conflict(String),
+    /// The id is already used where a unique one is required
+    Duplicate(String),
+    /// An invalid id, template, selector, input file or argument combination
+    Invalid(String),
+    /// Reading or writing a file failed
+    Io { path: String, source: io::Error },
+}
+
+impl KugiriError {
+    pub const EXIT_FAILURE: u8 = 1;
+    pub const EXIT_NOT_FOUND: u8 = 2;
+    pub const EXIT_MALFORMED: u8 = 3;
+    pub const EXIT_IO: u8 = 4;
+    pub const EXIT_INVALID: u8 = 5;
+    pub const EXIT_DUPLICATE: u8 = 6;
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        KugiriError::Invalid(message.into())
+    }
+
+    pub fn io(path: &str, source: io::Error) -> Self {
+        KugiriError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    /// The process exit code for this error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            KugiriError::SectionNotFound { .. }
+            | KugiriError::AnchorNotFound { .. }
+            | KugiriError::NoMatch(_) => Self::EXIT_NOT_FOUND,
+            KugiriError::Malformed(_) | KugiriError::Lint { .. } => Self::EXIT_MALFORMED,
+            KugiriError::Io { .. } => Self::EXIT_IO,
+            KugiriError::Command(_) | KugiriError::Conflict(_) | KugiriError::Drift { .. } => {
+                Self::EXIT_FAILURE
+            }
+            KugiriError::Operation { source, .. } => source.exit_code(),
+            KugiriError::Invalid(_) => Self::EXIT_INVALID,
+            KugiriError::Duplicate(_) => Self::EXIT_DUPLICATE,
+        }
+    }
+}
+
+impl fmt::Display for KugiriError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KugiriError::SectionNotFound { id } => write!(f, "Section with id '{id}' not found"),
+            KugiriError::AnchorNotFound { id } => write!(f, "Marker with id '{id}' not found"),
+            KugiriError::Malformed(problem) => problem.fmt(f),
+            KugiriError::Lint { problems } => write!(f, "Found {problems} marker problem(s)"),
+            KugiriError::NoMatch(message)
+            | KugiriError::Command(message)
+            | KugiriError::Conflict(message)
+            | KugiriError::Duplicate(message)
+            | KugiriError::Invalid(message) => f.write_str(message),
+            KugiriError::Io { path, source } => write!(f, "{path}: {source}"),
+            KugiriError::Operation {
+                index,
+                path,
+                source,
+            } => write!(f, "Operation {} on {path}: {source}", index + 1),
+            KugiriError::Drift { path } => {
+                write!(f, "{path} is out of date; run without --check to update it")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KugiriError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KugiriError::Malformed(problem) => Some(problem),
+            KugiriError::Io { source, .. } => Some(source),
+            KugiriError::Operation { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<MarkerProblem> for KugiriError {
+    fn from(problem: MarkerProblem) -> Self {
+        KugiriError::Malformed(problem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        let not_found = KugiriError::SectionNotFound {
+            id: "a".to_string(),
+        };
+        assert_eq!(not_found.exit_code(), 2);
+        assert_eq!(not_found.to_string(), "Section with id 'a' not found");
+
+        let malformed: KugiriError = MarkerProblem::OrphanEnd {
+            id: "a".to_string(),
+            line: 0,
+        }
+        .into();
+        assert_eq!(malformed.exit_code(), 3);
+
+        let io = KugiriError::io("missing.md", io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(io.exit_code(), 4);
+        assert!(io.to_string().starts_with("missing.md: "));
+
+        assert_eq!(KugiriError::invalid("bad").exit_code(), 5);
+        assert_eq!(KugiriError::Duplicate("dup".to_string()).exit_code(), 6);
+    }
+}
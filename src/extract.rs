@@ -1,11 +1,72 @@
-use crate::markers::find_section;
-use anyhow::Result;
+use crate::document::{Document, Occurrence, SectionNode};
+use crate::error::{KugiriError, Result};
+use crate::markers::MarkerSyntax;
+use crate::selector::Selector;
 
 pub fn extract(text: &str, id: &str) -> Result<String> {
-    let section = find_section(text, id)
-        .ok_or_else(|| anyhow::anyhow!("Section with id '{id}' not found"))?;
+    extract_with(text, id, &MarkerSyntax::default())
+}
+
+pub fn extract_with(text: &str, id: &str, syntax: &MarkerSyntax) -> Result<String> {
+    syntax.validate_id(id)?;
+    let doc = Document::parse(text, syntax);
+    Ok(doc.require(id)?.section.content.clone())
+}
+
+/// Extract the sections matched by `selector`, narrowed down by
+/// `occurrence`. Contents are joined with newlines.
+pub fn extract_selected(
+    text: &str,
+    selector: &Selector,
+    occurrence: Occurrence,
+    syntax: &MarkerSyntax,
+) -> Result<String> {
+    let doc = Document::parse(text, syntax);
+    let nodes = occurrence.select(selector.select(&doc)?, &selector.to_string())?;
+    Ok(nodes
+        .iter()
+        .map(|node| node.section.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Extract the sections whose attributes match all `key=value` filters,
+/// optionally restricted to one id, picking among them by `occurrence`.
+/// Contents are joined with newlines.
+pub fn extract_matching(
+    text: &str,
+    id: Option<&str>,
+    filters: &[(String, String)],
+    occurrence: Occurrence,
+    syntax: &MarkerSyntax,
+) -> Result<String> {
+    let doc = Document::parse(text, syntax);
+    let mut sections: Vec<&SectionNode> = match id {
+        Some(id) => {
+            syntax.validate_id(id)?;
+            doc.require_occurrences(id, Occurrence::All)?
+        }
+        None => doc.sections().collect(),
+    };
+    sections.retain(|node| node.section.matches_attributes(filters));
 
-    Ok(section.content)
+    if sections.is_empty() {
+        let filters = filters
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        return Err(KugiriError::NoMatch(format!(
+            "No section matches attributes {filters}"
+        )));
+    }
+
+    let sections = occurrence.select(sections, id.unwrap_or("*"))?;
+    Ok(sections
+        .iter()
+        .map(|node| node.section.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n"))
 }
 
 #[cfg(test)]
@@ -27,6 +88,70 @@ Footer"#;
         assert_eq!(result, "This is the content\nto extract");
     }
 
+    #[test]
+    fn test_extract_inline_section() {
+        let text = "Current version: <!-- KUGIRI-BEGIN: ver -->1.2.3<!-- KUGIRI-END: ver -->";
+        assert_eq!(extract(text, "ver").unwrap(), "1.2.3");
+    }
+
+    #[test]
+    fn test_extract_matching_attributes() {
+        let text = r#"<!-- KUGIRI-BEGIN: rust-example lang=rust -->
+fn main() {}
+<!-- KUGIRI-END: rust-example -->
+<!-- KUGIRI-BEGIN: go-example lang=go -->
+func main() {}
+<!-- KUGIRI-END: go-example -->"#;
+        let syntax = MarkerSyntax::default();
+        let lang = |value: &str| vec![("lang".to_string(), value.to_string())];
+
+        let result = extract_matching(text, None, &lang("go"), Occurrence::All, &syntax).unwrap();
+        assert_eq!(result, "func main() {}");
+
+        let result = extract_matching(
+            text,
+            Some("rust-example"),
+            &lang("rust"),
+            Occurrence::All,
+            &syntax,
+        )
+        .unwrap();
+        assert_eq!(result, "fn main() {}");
+
+        let result = extract_matching(
+            text,
+            Some("rust-example"),
+            &lang("go"),
+            Occurrence::All,
+            &syntax,
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No section matches attributes lang=go"));
+
+        let result = extract_matching(text, Some("missing"), &[], Occurrence::All, &syntax);
+        assert!(result.unwrap_err().to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_extract_matching_occurrence() {
+        let text = r#"<!-- KUGIRI-BEGIN: example lang=rust -->
+one
+<!-- KUGIRI-END: example -->
+<!-- KUGIRI-BEGIN: example lang=rust -->
+two
+<!-- KUGIRI-END: example -->"#;
+        let syntax = MarkerSyntax::default();
+        let extract =
+            |occurrence| extract_matching(text, Some("example"), &[], occurrence, &syntax);
+
+        assert_eq!(extract(Occurrence::First).unwrap(), "one");
+        assert_eq!(extract(Occurrence::Nth(2)).unwrap(), "two");
+        assert_eq!(extract(Occurrence::All).unwrap(), "one\ntwo");
+        assert!(extract(Occurrence::Nth(3)).is_err());
+    }
+
     #[test]
     fn test_extract_section_not_found() {
         let text = "Some text";
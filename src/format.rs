@@ -0,0 +1,243 @@
+use crate::attributes::normalize_attribute_text;
+use crate::check::fix_document;
+use crate::document::{Document, NodeKind};
+use crate::markers::{MarkerLine, MarkerSyntax};
+use crate::scanner::LineContext;
+use crate::utils::join_lines_preserve_trailing_newline;
+use std::collections::HashSet;
+
+/// Put the markers of `text` into their canonical layout:
+///
+/// - markers are spelled exactly as the templates, with single spaces
+///   between attributes, so `<!--KUGIRI-BEGIN:x-->` becomes
+///   `<!-- KUGIRI-BEGIN: x -->`
+/// - END markers are indented like their BEGIN marker
+/// - one blank line separates a section or INSERT marker from the text or
+///   section following it, blank lines before one collapse to a single
+///   line, and none are left at the start or end of the file
+///
+/// Spacing is only changed outside sections: section content, nested
+/// sections included, is left alone. Formatting formatted text changes
+/// nothing.
+pub fn format_markers(text: &str, syntax: &MarkerSyntax) -> String {
+    let spelled = spell_markers(text, syntax);
+    let (indented, _) = fix_document(&Document::parse(&spelled, syntax));
+    space_sections(&Document::parse(&indented, syntax))
+}
+
+fn spell_markers(text: &str, syntax: &MarkerSyntax) -> String {
+    let doc = Document::parse(text, syntax);
+    let render = |marker: MarkerLine| {
+        let attributes = normalize_attribute_text(marker.attributes);
+        syntax.marker_with_attributes(marker.kind, marker.id, &attributes)
+    };
+    let mut inline = doc
+        .sections()
+        .filter(|node| node.kind == NodeKind::Inline)
+        .peekable();
+
+    let mut result: Vec<String> = Vec::new();
+    for (idx, &line) in doc.lines().iter().enumerate() {
+        if doc.line_context(idx) != LineContext::Active {
+            result.push(line.to_string());
+            continue;
+        }
+
+        let mut spelled = String::new();
+        let mut pos = 0;
+        while let Some(span) = inline
+            .next_if(|node| node.section.start_line == idx)
+            .and_then(|node| node.section.inline.as_ref())
+        {
+            let begin = &line[span.outer.start..span.inner.start];
+            let end = &line[span.inner.end..span.outer.end];
+            spelled.push_str(&line[pos..span.outer.start]);
+            spelled.push_str(&syntax.parse_marker(begin).map_or(begin.to_string(), render));
+            spelled.push_str(&line[span.inner.clone()]);
+            spelled.push_str(&syntax.parse_marker(end).map_or(end.to_string(), render));
+            pos = span.outer.end;
+        }
+        if pos > 0 {
+            spelled.push_str(&line[pos..]);
+            result.push(spelled);
+            continue;
+        }
+
+        match syntax.parse_marker_loose(line) {
+            Some(marker) => {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                result.push(format!("{indent}{}", render(marker)));
+            }
+            None => result.push(line.to_string()),
+        }
+    }
+
+    join_lines_preserve_trailing_newline(result, text)
+}
+
+fn space_sections(doc: &Document) -> String {
+    let lines = doc.lines();
+    // Lines a section or INSERT marker ends on, lines one starts on, and the
+    // content lines of sections, whose spacing is left alone
+    let mut closes = HashSet::new();
+    let mut opens = HashSet::new();
+    let mut inside = vec![false; lines.len()];
+    for node in doc.nodes() {
+        if node.kind != NodeKind::Inline {
+            opens.insert(node.section.start_line);
+            closes.insert(node.section.end_line);
+        }
+        if node.kind == NodeKind::Block {
+            inside[node.section.start_line + 1..node.section.end_line].fill(true);
+        }
+    }
+
+    let blank = |line: usize| lines[line].trim().is_empty();
+    let mut result: Vec<String> = Vec::new();
+    let mut idx = 0;
+    while idx < lines.len() {
+        if inside[idx] {
+            result.push(lines[idx].to_string());
+            idx += 1;
+            continue;
+        }
+        if !blank(idx) {
+            // Separate a section from the text or section right after it
+            if idx > 0 && closes.contains(&(idx - 1)) && !inside[idx - 1] {
+                result.push(String::new());
+            }
+            result.push(lines[idx].to_string());
+            idx += 1;
+            continue;
+        }
+
+        let end = (idx..lines.len())
+            .find(|&line| !blank(line) || inside[line])
+            .unwrap_or(lines.len());
+        let after_marker = idx > 0 && closes.contains(&(idx - 1));
+        let before_marker = opens.contains(&end);
+        if after_marker || before_marker {
+            if idx > 0 && end < lines.len() {
+                result.push(String::new());
+            }
+        } else {
+            result.extend(lines[idx..end].iter().map(|line| line.to_string()));
+        }
+        idx = end;
+    }
+
+    join_lines_preserve_trailing_newline(result, doc.text())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(text: &str) -> String {
+        format_markers(text, &MarkerSyntax::default())
+    }
+
+    #[test]
+    fn test_format_spelling() {
+        assert_eq!(
+            format("<!--KUGIRI-BEGIN:x   lang=rust-->\nbody\n  <!--  KUGIRI-END:  x -->  \n"),
+            "<!-- KUGIRI-BEGIN: x lang=rust -->\nbody\n<!-- KUGIRI-END: x -->\n"
+        );
+        assert_eq!(
+            format("Version: <!-- KUGIRI-BEGIN: v  a=1 -->1.0<!-- KUGIRI-END: v --> ok\n"),
+            "Version: <!-- KUGIRI-BEGIN: v a=1 -->1.0<!-- KUGIRI-END: v --> ok\n"
+        );
+
+        let code = "```\n<!--KUGIRI-BEGIN:x-->\n```\n";
+        assert_eq!(
+            format_markers(code, &MarkerSyntax::default().with_markdown(true)),
+            code
+        );
+    }
+
+    #[test]
+    fn test_format_spacing() {
+        let text = r#"
+
+<!-- KUGIRI-BEGIN: a -->
+
+
+keep
+<!-- KUGIRI-END: a -->
+<!-- KUGIRI-BEGIN: b -->
+b
+<!-- KUGIRI-END: b -->
+
+
+
+Text
+
+
+<!-- KUGIRI-INSERT: end -->
+
+"#;
+        let formatted = format(text);
+        assert_eq!(
+            formatted,
+            r#"<!-- KUGIRI-BEGIN: a -->
+
+
+keep
+<!-- KUGIRI-END: a -->
+
+<!-- KUGIRI-BEGIN: b -->
+b
+<!-- KUGIRI-END: b -->
+
+Text
+
+<!-- KUGIRI-INSERT: end -->
+"#
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_separates_text() {
+        let text = "Intro\n<!-- KUGIRI-BEGIN: a -->\nx\n<!-- KUGIRI-END: a -->\nText after\n<!-- KUGIRI-INSERT: b -->\nMore\n";
+        assert_eq!(
+            format(text),
+            "Intro\n<!-- KUGIRI-BEGIN: a -->\nx\n<!-- KUGIRI-END: a -->\n\nText after\n<!-- KUGIRI-INSERT: b -->\n\nMore\n"
+        );
+    }
+
+    #[test]
+    fn test_format_keeps_nested_content() {
+        let text = r#"<!-- KUGIRI-BEGIN: outer -->
+Intro
+
+
+<!-- KUGIRI-BEGIN: inner -->
+x
+<!-- KUGIRI-END: inner -->
+After inner
+<!-- KUGIRI-END: outer -->
+"#;
+        let syntax = MarkerSyntax::default();
+        let content = |text: &str| {
+            Document::parse(text, &syntax)
+                .get("outer")
+                .unwrap()
+                .section
+                .content
+                .clone()
+        };
+
+        let formatted = format(text);
+        assert_eq!(formatted, text);
+        assert_eq!(content(&formatted), content(text));
+    }
+
+    #[test]
+    fn test_format_reindents_end() {
+        assert_eq!(
+            format("- item\n  <!--KUGIRI-BEGIN:a-->\n  x\n<!-- KUGIRI-END: a -->\n"),
+            "- item\n  <!-- KUGIRI-BEGIN: a -->\n  x\n  <!-- KUGIRI-END: a -->\n"
+        );
+    }
+}
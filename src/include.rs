@@ -0,0 +1,374 @@
+use crate::document::{outermost, Document, SectionNode};
+use crate::error::{KugiriError, Result};
+use crate::io::read_file;
+use crate::markers::MarkerSyntax;
+use crate::sync::fenced;
+use crate::update::{set_attributes, update_each};
+use std::path::{Path, PathBuf};
+
+/// Where the snippet of a section with a `src` attribute comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetSource {
+    pub path: String,
+    pub range: SnippetRange,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnippetRange {
+    /// The whole file
+    File,
+    /// 1-based inclusive line range, from `#L10-L30` or `#L10`
+    Lines(usize, usize),
+    /// A KUGIRI section in the source file, from `#kugiri=ID`
+    Section(String),
+}
+
+impl SnippetSource {
+    /// Parse `path`, `path#L10-L30`, `path#L10` or `path#kugiri=ID`.
+    pub fn parse(src: &str) -> Result<Self> {
+        let invalid = |reason: &str| KugiriError::invalid(format!("Invalid src '{src}': {reason}"));
+        let (path, fragment) = match src.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (src, None),
+        };
+        if path.is_empty() {
+            return Err(invalid("missing path"));
+        }
+
+        let range = match fragment {
+            None => SnippetRange::File,
+            Some(fragment) => {
+                if let Some(id) = fragment.strip_prefix("kugiri=") {
+                    if id.is_empty() {
+                        return Err(invalid("missing section id after 'kugiri='"));
+                    }
+                    SnippetRange::Section(id.to_string())
+                } else {
+                    let line = |text: &str| {
+                        text.strip_prefix('L')
+                            .and_then(|n| n.parse::<usize>().ok())
+                            .filter(|n| *n > 0)
+                    };
+                    let (start, end) = match fragment.split_once('-') {
+                        Some((start, end)) => (line(start), line(end)),
+                        None => (line(fragment), line(fragment)),
+                    };
+                    match (start, end) {
+                        (Some(start), Some(end)) if start <= end => SnippetRange::Lines(start, end),
+                        _ => return Err(invalid("expected '#L10-L30', '#L10' or '#kugiri=ID'")),
+                    }
+                }
+            }
+        };
+
+        Ok(SnippetSource {
+            path: path.to_string(),
+            range,
+        })
+    }
+}
+
+/// Where `src` paths are resolved.
+#[derive(Debug, Clone)]
+pub struct IncludeOptions {
+    /// Directory `src` paths are relative to, usually the edited file's
+    pub base: PathBuf,
+    /// Snippets must come from files inside this directory
+    pub root: PathBuf,
+}
+
+/// Refresh every section with a `src` attribute from the file it names:
+///
+/// ```markdown
+/// <!-- KUGIRI-BEGIN: example src="examples/basic.rs#L10-L30" fence=rust -->
+/// <!-- KUGIRI-BEGIN: usage src="src/lib.rs#kugiri=usage" -->
+/// ```
+///
+/// Snippets are dedented, and `fence=LANG` wraps them in a fenced code
+/// block. The [`content_hash`] of each snippet is recorded in a `hash`
+/// attribute, which `push` uses to tell which side changed since.
+/// `syntax_for` gives the marker syntax of `#kugiri=` source files.
+pub fn include_document(
+    doc: &Document,
+    options: &IncludeOptions,
+    syntax_for: impl Fn(&str) -> Result<MarkerSyntax>,
+) -> Result<String> {
+    let nodes: Vec<&SectionNode> = doc
+        .sections()
+        .filter(|node| node.section.attributes.contains_key("src"))
+        .collect();
+
+    let mut snippets = Vec::new();
+    for node in outermost(&nodes) {
+        let embedded = Embedded::resolve(node, options)?;
+        let snippet = dedent(&embedded.snippet(node, &syntax_for)?);
+        // Node indexes stay the same when only marker attributes change
+        snippets.push((node.index, content_hash(&snippet), snippet));
+    }
+
+    let hashes: Vec<_> = snippets
+        .iter()
+        .map(|(index, hash, _)| (doc.node(*index), "hash", hash.clone()))
+        .collect();
+    let text = set_attributes(doc, &hashes);
+    let doc = Document::parse(&text, doc.syntax());
+
+    let mut targets = Vec::new();
+    for (index, _, snippet) in snippets {
+        let node = doc.node(index);
+        targets.push((node, fenced(node, snippet)?));
+    }
+    let targets: Vec<(&SectionNode, &str)> = targets
+        .iter()
+        .map(|(node, snippet)| (*node, snippet.as_str()))
+        .collect();
+    update_each(&doc, &targets)
+}
+
+/// The file named by the `src` attribute of a section.
+pub(crate) struct Embedded {
+    pub source: SnippetSource,
+    pub path: String,
+    pub text: String,
+}
+
+impl Embedded {
+    /// Parse the `src` of `node` and read its file, refusing files outside
+    /// the repository root.
+    pub(crate) fn resolve(node: &SectionNode, options: &IncludeOptions) -> Result<Self> {
+        let Some(src) = node.section.attributes.get("src") else {
+            return Err(KugiriError::invalid(format!(
+                "Section '{}' has no src attribute",
+                node.id()
+            )));
+        };
+        let source = SnippetSource::parse(src).map_err(|err| in_section(node, err))?;
+
+        let path = options.base.join(&source.path);
+        if !canonical(&path)?.starts_with(canonical(&options.root)?) {
+            return Err(in_section(
+                node,
+                KugiriError::invalid(format!(
+                    "src '{}' is outside the repository root {}",
+                    source.path,
+                    options.root.display()
+                )),
+            ));
+        }
+        let path = path.to_string_lossy().into_owned();
+        let text = read_file(&path)?;
+        Ok(Embedded { source, path, text })
+    }
+
+    /// The snippet as written in the file, before dedenting.
+    pub(crate) fn snippet(
+        &self,
+        node: &SectionNode,
+        syntax_for: impl Fn(&str) -> Result<MarkerSyntax>,
+    ) -> Result<String> {
+        match &self.source.range {
+            SnippetRange::File => Ok(self.text.clone()),
+            SnippetRange::Lines(start, end) => {
+                let lines: Vec<&str> = self.text.lines().collect();
+                if *end > lines.len() {
+                    return Err(in_section(
+                        node,
+                        KugiriError::NoMatch(format!(
+                            "{} has {} lines, so L{start}-L{end} is out of range",
+                            self.path,
+                            lines.len()
+                        )),
+                    ));
+                }
+                Ok(lines[start - 1..*end].join("\n"))
+            }
+            SnippetRange::Section(id) => {
+                let doc = Document::parse(&self.text, &syntax_for(&self.path)?);
+                match doc.get(id) {
+                    Some(section) => Ok(section.section.content.clone()),
+                    None => Err(in_section(
+                        node,
+                        KugiriError::NoMatch(format!("no section '{id}' in {}", self.path)),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Prefix messages about a `src` attribute with the section they concern.
+pub(crate) fn in_section(node: &SectionNode, err: KugiriError) -> KugiriError {
+    match err {
+        KugiriError::Invalid(message) => {
+            KugiriError::invalid(format!("Section '{}': {message}", node.id()))
+        }
+        KugiriError::NoMatch(message) => {
+            KugiriError::NoMatch(format!("Section '{}': {message}", node.id()))
+        }
+        err => err,
+    }
+}
+
+/// A short, stable hash of snippet text, ignoring trailing newlines
+/// (64-bit FNV-1a in hex).
+pub fn content_hash(text: &str) -> String {
+    let hash = text
+        .trim_end_matches('\n')
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+    format!("{hash:016x}")
+}
+
+fn canonical(path: &Path) -> Result<PathBuf> {
+    let path = if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    };
+    path.canonicalize()
+        .map_err(|err| KugiriError::io(&path.to_string_lossy(), err))
+}
+
+/// The closest directory at or above `dir` containing `.git`, or `dir`
+/// itself outside a repository.
+pub fn repo_root(dir: &Path) -> PathBuf {
+    let start = canonical(dir).unwrap_or_else(|_| dir.to_path_buf());
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(&start)
+        .to_path_buf()
+}
+
+/// Remove the leading whitespace shared by all non-blank lines.
+pub fn dedent(text: &str) -> String {
+    let indent = common_indent(text);
+    text.lines()
+        .map(|line| line.strip_prefix(indent).unwrap_or(line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The leading whitespace shared by all non-blank lines of `text`.
+pub(crate) fn common_indent(text: &str) -> &str {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .reduce(|common, indent| {
+            let shared = common
+                .char_indices()
+                .zip(indent.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(common.len().min(indent.len()), |((i, _), _)| i);
+            &common[..shared]
+        })
+        .unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_parse_snippet_source() {
+        let parse = |src: &str| SnippetSource::parse(src).map(|source| source.range);
+
+        assert_eq!(parse("a.rs").unwrap(), SnippetRange::File);
+        assert_eq!(parse("a.rs#L3-L5").unwrap(), SnippetRange::Lines(3, 5));
+        assert_eq!(parse("a.rs#L7").unwrap(), SnippetRange::Lines(7, 7));
+        assert_eq!(
+            parse("src/lib.rs#kugiri=usage").unwrap(),
+            SnippetRange::Section("usage".to_string())
+        );
+        assert!(parse("a.rs#L5-L3").is_err());
+        assert!(parse("a.rs#L0").is_err());
+        assert!(parse("a.rs#10-20").is_err());
+        assert!(parse("#L1").is_err());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+        assert_eq!(content_hash("a\n\n"), content_hash("a"));
+        assert_ne!(content_hash("a"), content_hash("b"));
+    }
+
+    #[test]
+    fn test_dedent() {
+        assert_eq!(
+            dedent("    fn a() {\n\n        b();\n    }"),
+            "fn a() {\n\n    b();\n}"
+        );
+        assert_eq!(dedent("\tx\n\t\ty"), "x\n\ty");
+        assert_eq!(dedent("x\n  y"), "x\n  y");
+    }
+
+    fn include(dir: &Path, text: &str) -> Result<String> {
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        let options = IncludeOptions {
+            base: dir.join("docs"),
+            root: dir.to_path_buf(),
+        };
+        include_document(&doc, &options, |path| Ok(MarkerSyntax::for_path(path)))
+    }
+
+    #[test]
+    fn test_include_document() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(
+            dir.path().join("lib.rs"),
+            "mod a {\n    fn one() {}\n    fn two() {}\n}\n// KUGIRI-BEGIN: usage\nuse a::one;\n// KUGIRI-END: usage\n",
+        )
+        .unwrap();
+
+        let text = r#"<!-- KUGIRI-BEGIN: fns src="../lib.rs#L2-L3" fence=rust -->
+<!-- KUGIRI-END: fns -->
+<!-- KUGIRI-BEGIN: usage src="../lib.rs#kugiri=usage" -->
+old
+<!-- KUGIRI-END: usage -->
+"#;
+        assert_eq!(
+            include(dir.path(), text).unwrap(),
+            r#"<!-- KUGIRI-BEGIN: fns src="../lib.rs#L2-L3" fence=rust hash=6aaa6d0cc42f63db -->
+```rust
+fn one() {}
+fn two() {}
+```
+<!-- KUGIRI-END: fns -->
+<!-- KUGIRI-BEGIN: usage src="../lib.rs#kugiri=usage" hash=0dbe1003f2d8ed7a -->
+use a::one;
+<!-- KUGIRI-END: usage -->
+"#
+        );
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::write(repo.join("a.rs"), "one\ntwo\n").unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret\n").unwrap();
+
+        let err = |src: &str| {
+            let text = format!("<!-- KUGIRI-BEGIN: x src=\"{src}\" -->\n<!-- KUGIRI-END: x -->\n");
+            include(&repo, &text).unwrap_err()
+        };
+
+        let outside = err("../../secret.txt");
+        assert_eq!(outside.exit_code(), KugiriError::EXIT_INVALID);
+        assert!(outside.to_string().contains("outside the repository root"));
+
+        let range = err("../a.rs#L2-L5");
+        assert_eq!(range.exit_code(), KugiriError::EXIT_NOT_FOUND);
+        assert!(range.to_string().contains("has 2 lines"));
+
+        assert!(err("../a.rs#kugiri=missing")
+            .to_string()
+            .starts_with("Section 'x': no section 'missing'"));
+    }
+}
@@ -1,6 +1,7 @@
-use crate::markers::{find_marker_for_anchor, make_begin_marker, make_end_marker};
+use crate::document::Document;
+use crate::error::{KugiriError, Result};
+use crate::markers::MarkerSyntax;
 use crate::utils::join_lines_preserve_trailing_newline;
-use anyhow::{bail, Result};
 
 pub fn insert(
     text: &str,
@@ -9,17 +10,61 @@ pub fn insert(
     before: Option<&str>,
     after: Option<&str>,
 ) -> Result<String> {
+    insert_with(text, id, content, before, after, &MarkerSyntax::default())
+}
+
+pub fn insert_with(
+    text: &str,
+    id: &str,
+    content: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+    syntax: &MarkerSyntax,
+) -> Result<String> {
+    insert_document(
+        &Document::parse(text, syntax),
+        id,
+        content,
+        before,
+        after,
+        false,
+    )
+}
+
+/// Insert a new section into a parsed document. An id that is already used
+/// is refused unless `allow_duplicate` is set.
+pub fn insert_document(
+    doc: &Document,
+    id: &str,
+    content: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+    allow_duplicate: bool,
+) -> Result<String> {
+    let syntax = doc.syntax();
     // Validate parameters
     match (before, after) {
-        (Some(_), Some(_)) => bail!("Specify only one of --before or --after"),
-        (None, None) => bail!("Must specify --before or --after for insert command"),
+        (Some(_), Some(_)) => {
+            return Err(KugiriError::invalid(
+                "Specify only one of --before or --after",
+            ))
+        }
+        (None, None) => {
+            return Err(KugiriError::invalid(
+                "Must specify --before or --after for insert command",
+            ))
+        }
         _ => {}
     }
+    syntax.validate_id(id)?;
+    if let Some(existing) = doc.get(id).filter(|_| !allow_duplicate) {
+        return Err(KugiriError::Duplicate(format!("Section with id '{id}' already exists at line {}; use --allow-duplicate to add another",
+            existing.section.start_line + 1)));
+    }
 
     // Find the marker to insert relative to
     let marker_id = before.or(after).unwrap();
-    let marker_section = find_marker_for_anchor(text, marker_id)
-        .ok_or_else(|| anyhow::anyhow!("Marker with id '{marker_id}' not found"))?;
+    let marker_section = &doc.require_anchor(marker_id)?.section;
 
     // Trim trailing newline from content
     let content_trimmed = content.trim_end_matches('\n');
@@ -44,13 +89,13 @@ pub fn insert(
     let new_section = format!(
         "{}{}\n{}\n{}{}",
         marker_indent,
-        make_begin_marker(id),
+        syntax.begin_marker(id),
         indented_content,
         marker_indent,
-        make_end_marker(id)
+        syntax.end_marker(id)
     );
 
-    let lines: Vec<&str> = text.lines().collect();
+    let lines = doc.lines();
     let mut result = Vec::new();
 
     if before.is_some() {
@@ -75,7 +120,7 @@ pub fn insert(
 
     Ok(join_lines_preserve_trailing_newline(
         result.iter().map(|s| s.to_string()).collect(),
-        text,
+        doc.text(),
     ))
 }
 
@@ -141,6 +186,25 @@ Footer"#;
         assert!(new_end_pos < existing_pos);
     }
 
+    #[test]
+    fn test_insert_refuses_duplicate_id() {
+        let text = r#"<!-- KUGIRI-INSERT: top -->
+
+<!-- KUGIRI-BEGIN: v1.0 -->
+Notes
+<!-- KUGIRI-END: v1.0 -->"#;
+
+        let err = insert(text, "v1.0", "Again", None, Some("top")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Section with id 'v1.0' already exists at line 3; use --allow-duplicate to add another"
+        );
+
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        let result = insert_document(&doc, "v1.0", "Again", None, Some("top"), true).unwrap();
+        assert_eq!(result.matches("KUGIRI-BEGIN: v1.0").count(), 2);
+    }
+
     #[test]
     fn test_insert_marker_not_found() {
         let text = "Some text";
@@ -208,6 +272,33 @@ Outer content end
         assert!(new_section_line.starts_with("  "));
     }
 
+    #[test]
+    fn test_insert_with_hash_style() {
+        let text = "# KUGIRI-INSERT: deps\nname = \"demo\"\n";
+        let syntax = MarkerSyntax::new(crate::style::MarkerStyle::Hash);
+
+        let result =
+            insert_with(text, "extra", "serde = \"1\"", None, Some("deps"), &syntax).unwrap();
+
+        assert_eq!(
+            result,
+            "# KUGIRI-INSERT: deps\n\n# KUGIRI-BEGIN: extra\nserde = \"1\"\n# KUGIRI-END: extra\nname = \"demo\"\n"
+        );
+    }
+
+    #[test]
+    fn test_insert_rejects_id_with_marker_delimiter() {
+        let text = "<!-- KUGIRI-INSERT: top -->";
+
+        let result = insert(text, "bad-->id", "Content", None, Some("top"));
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("must not contain the marker delimiter '-->'"));
+    }
+
     #[test]
     fn test_insert_neither_before_nor_after_error() {
         let text = "Some text";
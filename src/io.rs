@@ -1,29 +1,45 @@
-use anyhow::Result;
+use crate::error::{KugiriError, Result};
 use std::fs;
 use std::io::{self, Read, Write as IoWrite};
 use std::path::Path;
 use tempfile::NamedTempFile;
 
+pub fn read_file(path: &str) -> Result<String> {
+    fs::read_to_string(path).map_err(|err| KugiriError::io(path, err))
+}
+
 pub fn read_file_or_stdin(path: Option<&str>) -> Result<String> {
     match path {
         Some("-") | None => {
             let mut buffer = String::new();
-            io::stdin().read_to_string(&mut buffer)?;
+            io::stdin()
+                .read_to_string(&mut buffer)
+                .map_err(|err| KugiriError::io("<stdin>", err))?;
             Ok(buffer)
         }
-        Some(path) => Ok(fs::read_to_string(path)?),
+        Some(path) => read_file(path),
     }
 }
 
 pub fn write_output(path: &str, content: &str, write_in_place: bool) -> Result<()> {
     if write_in_place {
+        // Leave identical files alone so their mtime doesn't change
+        if fs::read(path).is_ok_and(|existing| existing == content.as_bytes()) {
+            return Ok(());
+        }
+
         // Atomic write using tempfile
         let file_path = Path::new(path);
         let parent = file_path.parent().unwrap_or(Path::new("."));
 
-        let mut temp_file = NamedTempFile::new_in(parent)?;
-        temp_file.write_all(content.as_bytes())?;
-        temp_file.persist(path)?;
+        let mut temp_file =
+            NamedTempFile::new_in(parent).map_err(|err| KugiriError::io(path, err))?;
+        temp_file
+            .write_all(content.as_bytes())
+            .map_err(|err| KugiriError::io(path, err))?;
+        temp_file
+            .persist(path)
+            .map_err(|err| KugiriError::io(path, err.error))?;
     } else {
         // Write to stdout
         print!("{content}");
@@ -1,18 +1,62 @@
+pub mod apply;
+pub mod attributes;
+pub mod check;
+pub mod command;
+pub mod convert;
+pub mod copy;
+pub mod diagnostic;
+pub mod diff;
+pub mod document;
+pub mod error;
 pub mod extract;
+pub mod format;
+pub mod include;
 pub mod insert;
 pub mod io;
+pub mod list;
 pub mod markers;
+pub mod migrate;
+pub mod push;
+pub mod relocate;
 pub mod remove;
+pub mod rename;
+pub mod scanner;
+pub mod selector;
+pub mod style;
+pub mod sync;
+pub mod template;
 pub mod trim;
 pub mod update;
 pub mod upsert;
 pub mod utils;
 pub mod wrap;
 
+pub use apply::*;
+pub use attributes::*;
+pub use check::*;
+pub use command::*;
+pub use convert::*;
+pub use copy::*;
+pub use diagnostic::*;
+pub use diff::*;
+pub use document::*;
+pub use error::KugiriError;
 pub use extract::*;
+pub use format::*;
+pub use include::*;
 pub use insert::*;
+pub use list::*;
 pub use markers::*;
+pub use migrate::*;
+pub use push::*;
+pub use relocate::*;
 pub use remove::*;
+pub use rename::*;
+pub use scanner::*;
+pub use selector::*;
+pub use style::*;
+pub use sync::*;
+pub use template::*;
 pub use trim::*;
 pub use update::*;
 pub use upsert::*;
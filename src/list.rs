@@ -0,0 +1,278 @@
+use crate::attributes::Attributes;
+use crate::document::{Document, NodeKind, SectionNode};
+use crate::selector::Selector;
+use serde::Serialize;
+
+/// A section or INSERT marker as reported by `kugiri list`.
+///
+/// Line numbers are 1-based, as shown to users.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ListEntry {
+    pub id: String,
+    /// `section`, `inline` or `insert`
+    pub kind: &'static str,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub indent: String,
+    pub depth: usize,
+    /// Id of the enclosing section
+    pub parent: Option<String>,
+    pub attributes: Attributes,
+    /// Entries nested inside this one, filled in for tree output only
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ListEntry>,
+}
+
+impl ListEntry {
+    fn new(doc: &Document, node: &SectionNode) -> Self {
+        ListEntry {
+            id: node.id().to_string(),
+            kind: kind_name(node.kind),
+            start_line: node.section.start_line + 1,
+            end_line: node.section.end_line + 1,
+            indent: node.section.indent.clone(),
+            depth: node.depth,
+            parent: doc.parent(node).map(|parent| parent.id().to_string()),
+            attributes: node.section.attributes.clone(),
+            children: Vec::new(),
+        }
+    }
+
+    fn lines(&self) -> String {
+        if self.start_line == self.end_line {
+            self.start_line.to_string()
+        } else {
+            format!("{}-{}", self.start_line, self.end_line)
+        }
+    }
+}
+
+fn kind_name(kind: NodeKind) -> &'static str {
+    match kind {
+        NodeKind::Block => "section",
+        NodeKind::Inline => "inline",
+        NodeKind::Insert => "insert",
+    }
+}
+
+/// Every section and INSERT marker in document order, or only those matched
+/// by `selector`. Unlike the editing commands, an empty result is not an error.
+pub fn list_sections(doc: &Document, selector: Option<&Selector>) -> Vec<ListEntry> {
+    doc.nodes()
+        .iter()
+        .filter(|node| selector.is_none_or(|selector| selector.matches(doc, node)))
+        .map(|node| ListEntry::new(doc, node))
+        .collect()
+}
+
+/// Like [`list_sections`], with each entry nested under its closest listed
+/// ancestor.
+pub fn list_tree(doc: &Document, selector: Option<&Selector>) -> Vec<ListEntry> {
+    let roots: Vec<&SectionNode> = doc
+        .nodes()
+        .iter()
+        .filter(|node| node.parent.is_none())
+        .collect();
+    tree_entries(doc, &roots, selector)
+}
+
+fn tree_entries(
+    doc: &Document,
+    nodes: &[&SectionNode],
+    selector: Option<&Selector>,
+) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    for node in nodes {
+        let children: Vec<&SectionNode> = node.children.iter().map(|&i| doc.node(i)).collect();
+        let children = tree_entries(doc, &children, selector);
+        if selector.is_none_or(|selector| selector.matches(doc, node)) {
+            let mut entry = ListEntry::new(doc, node);
+            entry.children = children;
+            entries.push(entry);
+        } else {
+            // Lift the listed descendants of an unlisted node
+            entries.extend(children);
+        }
+    }
+    entries
+}
+
+/// Render entries as an aligned table with a header row.
+pub fn format_list(entries: &[ListEntry]) -> String {
+    let mut rows = vec![[
+        "ID".to_string(),
+        "KIND".to_string(),
+        "LINES".to_string(),
+        "INDENT".to_string(),
+        "DEPTH".to_string(),
+        "PARENT".to_string(),
+    ]];
+    for entry in entries {
+        rows.push([
+            entry.id.clone(),
+            entry.kind.to_string(),
+            entry.lines(),
+            entry.indent.chars().count().to_string(),
+            entry.depth.to_string(),
+            entry.parent.clone().unwrap_or_else(|| "-".to_string()),
+        ]);
+    }
+
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    for row in &rows {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Render entries from [`list_tree`] with box-drawing branches.
+pub fn format_tree(entries: &[ListEntry]) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        write_tree_entry(&mut out, entry, "", "");
+    }
+    out
+}
+
+/// Write `entry` after `lead` and its children below it, each line of them
+/// starting with `prefix`.
+fn write_tree_entry(out: &mut String, entry: &ListEntry, lead: &str, prefix: &str) {
+    let lines = if entry.start_line == entry.end_line {
+        format!("line {}", entry.start_line)
+    } else {
+        format!("lines {}", entry.lines())
+    };
+    out.push_str(&format!("{lead}{} ({}, {lines})\n", entry.id, entry.kind));
+
+    for (i, child) in entry.children.iter().enumerate() {
+        if i + 1 == entry.children.len() {
+            write_tree_entry(
+                out,
+                child,
+                &format!("{prefix}└── "),
+                &format!("{prefix}    "),
+            );
+        } else {
+            write_tree_entry(
+                out,
+                child,
+                &format!("{prefix}├── "),
+                &format!("{prefix}│   "),
+            );
+        }
+    }
+}
+
+/// Render entries as pretty-printed JSON.
+pub fn format_json(entries: &[ListEntry]) -> String {
+    let mut json = serde_json::to_string_pretty(entries).expect("entries serialize to JSON");
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markers::MarkerSyntax;
+
+    const TEXT: &str = r#"<!-- KUGIRI-INSERT: top -->
+<!-- KUGIRI-BEGIN: guide -->
+  <!-- KUGIRI-BEGIN: example lang=rust -->
+  fn main() {}
+  <!-- KUGIRI-END: example -->
+<!-- KUGIRI-END: guide -->
+Version <!-- KUGIRI-BEGIN: ver -->1.0<!-- KUGIRI-END: ver -->"#;
+
+    #[test]
+    fn test_list_sections() {
+        let doc = Document::parse(TEXT, &MarkerSyntax::default());
+        let entries = list_sections(&doc, None);
+
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.id.as_str(), entry.kind, entry.lines(), entry.depth))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("top", "insert", "1".to_string(), 0),
+                ("guide", "section", "2-6".to_string(), 0),
+                ("example", "section", "3-5".to_string(), 1),
+                ("ver", "inline", "7".to_string(), 0),
+            ]
+        );
+        assert_eq!(entries[2].parent.as_deref(), Some("guide"));
+        assert_eq!(entries[2].indent, "  ");
+        assert_eq!(entries[2].attributes["lang"], "rust");
+    }
+
+    #[test]
+    fn test_format_list() {
+        let doc = Document::parse(TEXT, &MarkerSyntax::default());
+        assert_eq!(
+            format_list(&list_sections(&doc, None)),
+            "ID       KIND     LINES  INDENT  DEPTH  PARENT
+top      insert   1      0       0      -
+guide    section  2-6    0       0      -
+example  section  3-5    2       1      guide
+ver      inline   7      0       0      -
+"
+        );
+    }
+
+    #[test]
+    fn test_format_tree() {
+        let doc = Document::parse(TEXT, &MarkerSyntax::default());
+        assert_eq!(
+            format_tree(&list_tree(&doc, None)),
+            "top (insert, line 1)
+guide (section, lines 2-6)
+└── example (section, lines 3-5)
+ver (inline, line 7)
+"
+        );
+
+        // Unselected parents are skipped and their matches lifted
+        let selector = Selector::parse("[lang]").unwrap();
+        assert_eq!(
+            format_tree(&list_tree(&doc, Some(&selector))),
+            "example (section, lines 3-5)\n"
+        );
+    }
+
+    #[test]
+    fn test_format_json() {
+        let doc = Document::parse(TEXT, &MarkerSyntax::default());
+        let selector = Selector::parse("ver").unwrap();
+        assert_eq!(
+            format_json(&list_sections(&doc, Some(&selector))),
+            r#"[
+  {
+    "id": "ver",
+    "kind": "inline",
+    "start_line": 7,
+    "end_line": 7,
+    "indent": "",
+    "depth": 0,
+    "parent": null,
+    "attributes": {}
+  }
+]
+"#
+        );
+    }
+}
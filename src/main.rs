@@ -1,11 +1,22 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use kugiri::{extract, insert, remove, trim, update, upsert, wrap};
-use std::fs;
-use std::io::Read;
-
-mod io;
-use io::{read_file_or_stdin, write_output};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use kugiri::error::Result;
+use kugiri::io::{read_file, read_file_or_stdin, write_output};
+use kugiri::{
+    allowed_env, check_document, colorize_diff, convert, copy_section, extract_matching,
+    extract_selected, fix_document, format_json, format_list, format_markers, format_tree,
+    include_document, insert_document, list_sections, list_tree, migrate_markers, move_section,
+    parse_attribute_filter, push_section, remove_occurrences, remove_selected, rename_references,
+    rename_section, repo_root, sync_document, trim_selected, trim_with, unified_diff,
+    update_occurrences, update_selected, upsert_document, validate_prefix, wrap_with,
+    CommandOptions, ConvertFrom, Diagnostic, Document, FileChange, IncludeOptions, KugiriError,
+    Manifest, MarkerStyle, MarkerSyntax, MarkerTemplate, MoveTarget, Occurrence, SectionRef,
+    Selector, Severity,
+};
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
 const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), env!("GIT_VERSION_SUFFIX"));
 
@@ -22,6 +33,12 @@ const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), env!("GIT_VERSION_SUFFI
 
   Where {id} is a unique identifier for the section.
 
+  The comment style is picked from the file extension (e.g. '// KUGIRI-BEGIN: {id}'
+  for .rs, '# KUGIRI-BEGIN: {id}' for .py/.yml/.sh) and can be overridden with
+  --style html|slash|hash|dash|semicolon|percent|block|mdx|rst.
+  Use --marker-prefix to replace the KUGIRI keyword (e.g. '<!-- DOCGEN-BEGIN: {id} -->'),
+  or --begin-template/--end-template for fully custom markers (e.g. '# >>> {id} >>>').
+
 EXAMPLES:
   # Extract content from a section
   kugiri extract README.md --id installation
@@ -35,14 +52,238 @@ EXAMPLES:
   # Update existing section or create new one if not found
   echo \"Content\" | kugiri upsert file.md --id section --after other-id --write
 
+  # Regenerate sections that have a cmd attribute, and fail CI when they are stale
+  kugiri sync README.md -w
+  kugiri sync README.md --check
+
+  # Refresh code examples embedded from other files with a src attribute
+  kugiri include README.md -w
+
+  # Write a fix made in an embedded snippet back to its source file
+  kugiri push README.md --id example -w
+
+  # Reorder changelog entries in one atomic edit
+  kugiri move CHANGELOG.md --id v1.1.0 --before v1.0.0 -w
+
+  # Rename a section and the src= and manifest references to it
+  kugiri rename README.md --id usage --to cli-usage --all-files -w
+
+  # Copy a section into another file, creating it after an anchor if missing
+  kugiri copy --from docs/cli.md#usage --to README.md#usage --create-after intro -w
+
+  # Run every operation of a manifest, writing each file once
+  kugiri apply kugiri.yaml -w
+
+  # Fail CI when markers are broken
+  kugiri check README.md docs/*.md
+
+  # Normalize marker spacing and blank lines around sections
+  kugiri fmt README.md docs/*.md -w
+
+  # List the sections of a file
+  kugiri list README.md --tree
+
   # Remove all marker lines from output
   kugiri trim file.md > clean.md
 
   # Wrap content with markers
-  echo \"Content to wrap\" | kugiri wrap --id section-name")]
+  echo \"Content to wrap\" | kugiri wrap --id section-name
+
+  # Rename the marker keyword of every marker in a file
+  kugiri migrate-markers README.md --to-prefix DOCGEN --write")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    #[command(flatten)]
+    markers: MarkerArgs,
+    /// Color error messages: auto (when stderr is a terminal and NO_COLOR is unset), always or never
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ListFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color what is written to `stream`.
+    fn enabled(self, stream: impl IsTerminal) -> bool {
+        match self {
+            ColorChoice::Auto => std::env::var_os("NO_COLOR").is_none() && stream.is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Args)]
+struct MarkerArgs {
+    /// Marker comment style (default: detected from the file extension)
+    #[arg(long, global = true)]
+    style: Option<MarkerStyle>,
+    /// Marker keyword used in place of KUGIRI (e.g. DOCGEN)
+    #[arg(long, global = true, value_parser = parse_prefix)]
+    marker_prefix: Option<String>,
+    /// Custom BEGIN marker template (e.g. '# >>> {id} >>>')
+    #[arg(long, global = true, requires = "end_template")]
+    begin_template: Option<MarkerTemplate>,
+    /// Custom END marker template (e.g. '# <<< {id} <<<')
+    #[arg(long, global = true, requires = "begin_template")]
+    end_template: Option<MarkerTemplate>,
+    /// Custom INSERT marker template
+    #[arg(long, global = true, requires = "begin_template")]
+    insert_template: Option<MarkerTemplate>,
+}
+
+impl MarkerArgs {
+    fn syntax_for(&self, file: &str) -> Result<MarkerSyntax> {
+        let mut syntax = MarkerSyntax::for_path(file);
+        if let Some(style) = self.style {
+            syntax = syntax.with_style(style);
+        }
+        if let Some(prefix) = &self.marker_prefix {
+            syntax = syntax.with_prefix(prefix);
+        }
+        if let (Some(begin), Some(end)) = (&self.begin_template, &self.end_template) {
+            syntax =
+                syntax.with_templates(begin.clone(), end.clone(), self.insert_template.clone())?;
+        }
+        Ok(syntax)
+    }
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Write changes in-place
+    #[arg(short, long)]
+    write: bool,
+    /// Print a diff and exit with 1 if the file would change, without writing it
+    #[arg(long, conflicts_with_all = ["write", "diff", "patch"])]
+    check: bool,
+    /// Print a unified diff of the changes instead of the whole file
+    #[arg(long)]
+    diff: bool,
+    /// Save the changes as a patch for `git apply` ('-' for stdout)
+    #[arg(long, value_name = "FILE")]
+    patch: Option<String>,
+}
+
+impl OutputArgs {
+    /// Print, write or check `result`, the edited `original` text of `file`.
+    fn emit(&self, file: &str, original: &str, result: &str, color: ColorChoice) -> Result<()> {
+        let change = FileChange {
+            path: file.to_string(),
+            original: original.to_string(),
+            result: result.to_string(),
+        };
+        self.emit_all(&[change], color)
+    }
+
+    /// Like [`OutputArgs::emit`] for several files: `--check` reports every
+    /// file that would change before failing on the first, and `--patch`
+    /// saves one patch covering all of them.
+    fn emit_all(&self, changes: &[FileChange], color: ColorChoice) -> Result<()> {
+        let diffs: Vec<String> = changes
+            .iter()
+            .map(|change| unified_diff(&change.path, &change.original, &change.result))
+            .collect();
+
+        if self.check {
+            let mut drift = None;
+            for (change, diff) in changes.iter().zip(&diffs) {
+                if change.result != change.original {
+                    print_diff(diff, color);
+                    drift.get_or_insert_with(|| change.path.clone());
+                }
+            }
+            return match drift {
+                Some(path) => Err(KugiriError::Drift { path }),
+                None => Ok(()),
+            };
+        }
+
+        match self.patch.as_deref() {
+            Some("-") => print!("{}", diffs.concat()),
+            Some(patch) => write_output(patch, &diffs.concat(), true)?,
+            None => {}
+        }
+        for (change, diff) in changes.iter().zip(&diffs) {
+            if self.diff {
+                print_diff(diff, color);
+            }
+            if self.write {
+                write_output(&change.path, &change.result, true)?;
+            } else if self.to_stdout() {
+                write_output(&change.path, &change.result, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the result is printed rather than written, diffed or checked.
+    fn to_stdout(&self) -> bool {
+        !(self.write || self.check || self.diff || self.patch.is_some())
+    }
+}
+
+/// `src` paths are relative to `file`, and stay inside `root` or the
+/// enclosing repository.
+fn include_options(file: &str, root: Option<String>) -> IncludeOptions {
+    let base = Path::new(file).parent().unwrap_or(Path::new(""));
+    IncludeOptions {
+        base: base.to_path_buf(),
+        root: root.map_or_else(|| repo_root(base), PathBuf::from),
+    }
+}
+
+fn print_diff(diff: &str, color: ColorChoice) {
+    if color.enabled(std::io::stdout()) {
+        print!("{}", colorize_diff(diff));
+    } else {
+        print!("{diff}");
+    }
+}
+
+#[derive(Args)]
+struct OccurrenceArgs {
+    /// Act on the N-th section with the id (counting from 1)
+    #[arg(long, conflicts_with = "all")]
+    occurrence: Option<NonZeroUsize>,
+    /// Act on every section with the id
+    #[arg(long)]
+    all: bool,
+}
+
+impl OccurrenceArgs {
+    fn occurrence(&self, default: Occurrence) -> Occurrence {
+        match (self.occurrence, self.all) {
+            (Some(n), _) => Occurrence::Nth(n.get()),
+            (None, true) => Occurrence::All,
+            (None, false) => default,
+        }
+    }
+}
+
+fn parse_attr(filter: &str) -> Result<(String, String)> {
+    parse_attribute_filter(filter).ok_or_else(|| {
+        KugiriError::invalid(format!(
+            "Invalid attribute filter '{filter}' (expected KEY=VALUE)"
+        ))
+    })
+}
+
+fn parse_prefix(prefix: &str) -> Result<String> {
+    validate_prefix(prefix)?;
+    Ok(prefix.to_string())
 }
 
 #[derive(Subcommand)]
@@ -63,23 +304,29 @@ enum Commands {
         /// Insert after this marker ID
         #[arg(long, conflicts_with = "before")]
         after: Option<String>,
-        /// Write changes in-place
-        #[arg(short, long)]
-        write: bool,
+        /// Insert even if a section with this ID already exists
+        #[arg(long)]
+        allow_duplicate: bool,
+        #[command(flatten)]
+        output: OutputArgs,
     },
     /// Update an existing section identified by --id
     Update {
         /// File to edit
         file: String,
         /// Section ID to update
-        #[arg(long)]
-        id: String,
+        #[arg(long, required_unless_present = "select")]
+        id: Option<String>,
+        /// Update every section matched by a selector (e.g. 'guide/example')
+        #[arg(long, conflicts_with = "id")]
+        select: Option<Selector>,
         /// Content file (default: stdin, use '-' for stdin explicitly)
         #[arg(long)]
         body_file: Option<String>,
-        /// Write changes in-place
-        #[arg(short, long)]
-        write: bool,
+        #[command(flatten)]
+        occurrence: OccurrenceArgs,
+        #[command(flatten)]
+        output: OutputArgs,
     },
     /// Update existing section or insert if not found
     Upsert {
@@ -97,34 +344,200 @@ enum Commands {
         /// Insert after this marker ID (for new sections)
         #[arg(long, conflicts_with = "before")]
         after: Option<String>,
-        /// Write changes in-place
-        #[arg(short, long)]
-        write: bool,
+        /// Insert a duplicate ID, or update the first of several sections with it
+        #[arg(long)]
+        allow_duplicate: bool,
+        #[command(flatten)]
+        output: OutputArgs,
     },
     /// Remove a section and its markers
     Remove {
         /// File to edit
         file: String,
         /// Section ID to remove
-        #[arg(long)]
-        id: String,
-        /// Write changes in-place
-        #[arg(short, long)]
-        write: bool,
+        #[arg(long, required_unless_present = "select")]
+        id: Option<String>,
+        /// Remove every section matched by a selector (e.g. 'v0.*')
+        #[arg(long, conflicts_with = "id")]
+        select: Option<Selector>,
+        #[command(flatten)]
+        occurrence: OccurrenceArgs,
+        #[command(flatten)]
+        output: OutputArgs,
     },
     /// Print inner content of a section (without markers)
     Extract {
         /// File to read
         file: String,
         /// Section ID to extract
+        #[arg(long, required_unless_present_any = ["attr", "select"])]
+        id: Option<String>,
+        /// Extract every section matched by a selector (e.g. '*[lang=rust]')
+        #[arg(long, conflicts_with_all = ["id", "attr"])]
+        select: Option<Selector>,
+        /// Only extract sections with this attribute (KEY=VALUE, repeatable)
+        #[arg(long, value_parser = parse_attr)]
+        attr: Vec<(String, String)>,
+        #[command(flatten)]
+        occurrence: OccurrenceArgs,
+    },
+    /// Apply the operations of a YAML or JSON manifest, writing each file once
+    Apply {
+        /// Manifest listing the operations
+        manifest: String,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Regenerate sections with a `cmd` attribute from their command's output
+    Sync {
+        /// File to edit
+        file: String,
+        /// Seconds a command may run before it is killed
+        #[arg(long, value_name = "SECS", default_value_t = 60)]
+        timeout: u64,
+        /// Directory to run commands in (default: the file's directory)
+        #[arg(long, value_name = "DIR")]
+        cwd: Option<String>,
+        /// Pass this environment variable to commands, besides PATH and HOME
+        #[arg(long = "allow-env", value_name = "NAME")]
+        allow_env: Vec<String>,
+        /// Pass the whole environment to commands
+        #[arg(long, conflicts_with = "allow_env")]
+        inherit_env: bool,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Refresh sections with a `src` attribute from the files they embed
+    Include {
+        /// File to edit
+        file: String,
+        /// Refuse snippets from outside this directory (default: the
+        /// enclosing git repository, or the file's directory)
+        #[arg(long, value_name = "DIR")]
+        root: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Write an edited `src` section back to the file it embeds
+    Push {
+        /// File containing the section
+        file: String,
+        /// Section ID to push
         #[arg(long)]
         id: String,
+        /// Overwrite the source even if it changed since the last sync
+        #[arg(long)]
+        force: bool,
+        /// Refuse sources from outside this directory (default: the
+        /// enclosing git repository, or the file's directory)
+        #[arg(long, value_name = "DIR")]
+        root: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Move a section, markers included, before or after a marker or into a section
+    Move {
+        /// File to edit
+        file: String,
+        /// Section ID to move
+        #[arg(long)]
+        id: String,
+        /// Move before this marker ID
+        #[arg(long, conflicts_with_all = ["after", "into"])]
+        before: Option<String>,
+        /// Move after this marker ID
+        #[arg(long, conflicts_with = "into")]
+        after: Option<String>,
+        /// Move to the end of this section's content
+        #[arg(long, value_name = "PARENT")]
+        into: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Rename a section, rewriting its BEGIN and END markers
+    Rename {
+        /// File to edit
+        file: String,
+        /// Section ID to rename
+        #[arg(long)]
+        id: String,
+        /// New section ID
+        #[arg(long, value_name = "NEW_ID")]
+        to: String,
+        /// Also rename INSERT markers with this id
+        #[arg(long)]
+        insert_markers: bool,
+        /// Also update src= attributes and apply manifests that refer to the
+        /// section, anywhere in the repository
+        #[arg(long)]
+        all_files: bool,
+        /// Directory searched by --all-files (default: the enclosing git
+        /// repository, or the file's directory)
+        #[arg(long, value_name = "DIR", requires = "all_files")]
+        root: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Copy a section's content into a section of another (or the same) file
+    Copy {
+        /// Section to copy, as 'path#id'
+        #[arg(long, value_name = "PATH#ID")]
+        from: SectionRef,
+        /// Section to update, as 'path#id'
+        #[arg(long, value_name = "PATH#ID")]
+        to: SectionRef,
+        /// Create the target section before this marker ID if it is missing
+        #[arg(long, value_name = "ID", conflicts_with = "create_after")]
+        create_before: Option<String>,
+        /// Create the target section after this marker ID if it is missing
+        #[arg(long, value_name = "ID")]
+        create_after: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Check markers for problems and exit non-zero if any errors are found
+    Check {
+        /// Files to check
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Repair the problems that can be fixed safely (END marker indentation);
+        /// the problems left afterwards are reported
+        #[arg(long)]
+        fix: bool,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Normalize marker spelling, END marker indentation and the blank lines
+    /// around sections
+    Fmt {
+        /// Files to format
+        #[arg(required = true)]
+        files: Vec<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// List the sections and INSERT markers of a file
+    List {
+        /// File to read
+        file: String,
+        /// Show sections nested under their parents
+        #[arg(long)]
+        tree: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+        /// Only list sections matched by a selector
+        #[arg(long)]
+        select: Option<Selector>,
     },
     /// Output the file with all marker lines removed
     Trim {
         /// File to read (use '-' for stdin)
         #[arg(default_value = "-")]
         file: String,
+        /// Only remove the markers of sections matched by a selector
+        #[arg(long)]
+        select: Option<Selector>,
     },
     /// Wrap content with KUGIRI markers
     Wrap {
@@ -135,11 +548,111 @@ enum Commands {
         #[arg(long)]
         body_file: Option<String>,
     },
+    /// Rewrite all markers from one prefix/style to another
+    MigrateMarkers {
+        /// File to edit
+        file: String,
+        /// New marker comment style (default: keep the current style)
+        #[arg(long)]
+        to_style: Option<MarkerStyle>,
+        /// New marker keyword (default: keep the current prefix)
+        #[arg(long, value_parser = parse_prefix)]
+        to_prefix: Option<String>,
+        /// New BEGIN marker template
+        #[arg(long, requires = "to_end_template")]
+        to_begin_template: Option<MarkerTemplate>,
+        /// New END marker template
+        #[arg(long, requires = "to_begin_template")]
+        to_end_template: Option<MarkerTemplate>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Convert regions of another doc-injection tool into KUGIRI sections
+    Convert {
+        /// File to edit
+        file: String,
+        /// Tool that wrote the regions (markdown-magic, mdsh, cog, embedme)
+        #[arg(long)]
+        from: ConvertFrom,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) => {
+            let _ = err.print();
+            // Usage errors share the exit code of other invalid input
+            return if err.use_stderr() {
+                ExitCode::from(KugiriError::EXIT_INVALID)
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
+
+    let color = cli.color.enabled(std::io::stderr());
+    let source = cli
+        .command
+        .file()
+        .map(|file| (file.to_string(), cli.markers.syntax_for(file)));
 
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprint!("{}", report(&err, source, color));
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+/// Render `err` as a diagnostic pointing into the command's input file.
+/// The file is read again so that commands don't have to hand it back.
+fn report(
+    err: &KugiriError,
+    source: Option<(String, Result<MarkerSyntax>)>,
+    color: bool,
+) -> String {
+    if let Some((path, Ok(syntax))) = source {
+        if let Ok(text) = read_file(&path) {
+            let doc = Document::parse(&text, &syntax);
+            return Diagnostic::from_error(err, &path, &doc).render(doc.lines(), color);
+        }
+    }
+    Diagnostic::new(err.to_string()).render(&[], color)
+}
+
+impl Commands {
+    /// The file the command reads markers from, if it has one.
+    fn file(&self) -> Option<&str> {
+        match self {
+            Commands::Insert { file, .. }
+            | Commands::Update { file, .. }
+            | Commands::Upsert { file, .. }
+            | Commands::Remove { file, .. }
+            | Commands::Extract { file, .. }
+            | Commands::List { file, .. }
+            | Commands::MigrateMarkers { file, .. }
+            | Commands::Convert { file, .. }
+            | Commands::Sync { file, .. }
+            | Commands::Include { file, .. }
+            | Commands::Push { file, .. }
+            | Commands::Move { file, .. }
+            | Commands::Rename { file, .. } => Some(file),
+            Commands::Trim { file, .. } if file != "-" => Some(file),
+            Commands::Trim { .. }
+            | Commands::Wrap { .. }
+            | Commands::Check { .. }
+            | Commands::Fmt { .. }
+            | Commands::Copy { .. }
+            | Commands::Apply { .. } => None,
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
     match cli.command {
         Commands::Insert {
             file,
@@ -147,23 +660,51 @@ fn main() -> Result<()> {
             body_file,
             before,
             after,
-            write,
+            allow_duplicate,
+            output,
         } => {
-            let text = fs::read_to_string(&file)?;
+            let text = read_file(&file)?;
             let body = read_file_or_stdin(body_file.as_deref())?;
-            let result = insert(&text, &id, &body, before.as_deref(), after.as_deref())?;
-            write_output(&file, &result, write)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&file)?);
+            let result = insert_document(
+                &doc,
+                &id,
+                &body,
+                before.as_deref(),
+                after.as_deref(),
+                allow_duplicate,
+            )?;
+            output.emit(&file, &text, &result, cli.color)?;
         }
         Commands::Update {
             file,
             id,
+            select,
             body_file,
-            write,
+            occurrence,
+            output,
         } => {
-            let text = fs::read_to_string(&file)?;
+            let text = read_file(&file)?;
             let body = read_file_or_stdin(body_file.as_deref())?;
-            let result = update(&text, &id, &body)?;
-            write_output(&file, &result, write)?;
+            let syntax = cli.markers.syntax_for(&file)?;
+            let result = match (select, id) {
+                (Some(selector), _) => update_selected(
+                    &text,
+                    &selector,
+                    &body,
+                    occurrence.occurrence(Occurrence::All),
+                    &syntax,
+                )?,
+                (None, Some(id)) => update_occurrences(
+                    &text,
+                    &id,
+                    &body,
+                    occurrence.occurrence(Occurrence::First),
+                    &syntax,
+                )?,
+                (None, None) => unreachable!("clap requires --id or --select"),
+            };
+            output.emit(&file, &text, &result, cli.color)?;
         }
         Commands::Upsert {
             file,
@@ -171,39 +712,347 @@ fn main() -> Result<()> {
             body_file,
             before,
             after,
-            write,
+            allow_duplicate,
+            output,
         } => {
-            let text = fs::read_to_string(&file)?;
+            let text = read_file(&file)?;
             let body = read_file_or_stdin(body_file.as_deref())?;
-            let result = upsert(&text, &id, &body, before.as_deref(), after.as_deref())?;
-            write_output(&file, &result, write)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&file)?);
+            let result = upsert_document(
+                &doc,
+                &id,
+                &body,
+                before.as_deref(),
+                after.as_deref(),
+                allow_duplicate,
+            )?;
+            output.emit(&file, &text, &result, cli.color)?;
         }
-        Commands::Remove { file, id, write } => {
-            let text = fs::read_to_string(&file)?;
-            let result = remove(&text, &id)?;
-            write_output(&file, &result, write)?;
+        Commands::Remove {
+            file,
+            id,
+            select,
+            occurrence,
+            output,
+        } => {
+            let text = read_file(&file)?;
+            let syntax = cli.markers.syntax_for(&file)?;
+            let result = match (select, id) {
+                (Some(selector), _) => remove_selected(
+                    &text,
+                    &selector,
+                    occurrence.occurrence(Occurrence::All),
+                    &syntax,
+                )?,
+                (None, Some(id)) => remove_occurrences(
+                    &text,
+                    &id,
+                    occurrence.occurrence(Occurrence::First),
+                    &syntax,
+                )?,
+                (None, None) => unreachable!("clap requires --id or --select"),
+            };
+            output.emit(&file, &text, &result, cli.color)?;
         }
-        Commands::Extract { file, id } => {
-            let text = fs::read_to_string(&file)?;
-            let result = extract(&text, &id)?;
+        Commands::Extract {
+            file,
+            id,
+            select,
+            attr,
+            occurrence,
+        } => {
+            let text = read_file(&file)?;
+            let syntax = cli.markers.syntax_for(&file)?;
+            if let Some(selector) = select {
+                let occurrence = occurrence.occurrence(Occurrence::All);
+                println!(
+                    "{}",
+                    extract_selected(&text, &selector, occurrence, &syntax)?
+                );
+                return Ok(());
+            }
+            // Without an id, every section matching the attributes is printed
+            let default = if id.is_some() {
+                Occurrence::First
+            } else {
+                Occurrence::All
+            };
+            let result = extract_matching(
+                &text,
+                id.as_deref(),
+                &attr,
+                occurrence.occurrence(default),
+                &syntax,
+            )?;
             println!("{result}");
         }
-        Commands::Trim { file } => {
-            let text = if file == "-" {
-                let mut buffer = String::new();
-                std::io::stdin().read_to_string(&mut buffer)?;
-                buffer
+        Commands::Apply { manifest, output } => {
+            if output.to_stdout() {
+                return Err(KugiriError::invalid(
+                    "apply can change several files; pass -w, --diff, --check or --patch",
+                ));
+            }
+            let base = Path::new(&manifest).parent().unwrap_or(Path::new(""));
+            let changes =
+                Manifest::load(&manifest)?.plan(base, |file| cli.markers.syntax_for(file))?;
+            // Every operation succeeded before anything is written
+            output.emit_all(&changes, cli.color)?;
+        }
+        Commands::Sync {
+            file,
+            timeout,
+            cwd,
+            allow_env,
+            inherit_env,
+            output,
+        } => {
+            let text = read_file(&file)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&file)?);
+            let cwd = match cwd {
+                Some(cwd) => PathBuf::from(cwd),
+                None => Path::new(&file)
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .to_path_buf(),
+            };
+            let options = CommandOptions {
+                cwd,
+                timeout: Some(Duration::from_secs(timeout)),
+                env: (!inherit_env).then(|| allowed_env(&allow_env)),
+            };
+            let result = sync_document(&doc, &options)?;
+            output.emit(&file, &text, &result, cli.color)?;
+        }
+        Commands::Include { file, root, output } => {
+            let text = read_file(&file)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&file)?);
+            let options = include_options(&file, root);
+            let result = include_document(&doc, &options, |path| cli.markers.syntax_for(path))?;
+            output.emit(&file, &text, &result, cli.color)?;
+        }
+        Commands::Push {
+            file,
+            id,
+            force,
+            root,
+            output,
+        } => {
+            let text = read_file(&file)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&file)?);
+            let options = include_options(&file, root);
+            let changes = push_section(&doc, &file, &id, &options, force, |path| {
+                cli.markers.syntax_for(path)
+            })?;
+            output.emit_all(&changes, cli.color)?;
+        }
+        Commands::Move {
+            file,
+            id,
+            before,
+            after,
+            into,
+            output,
+        } => {
+            let target = match (before, after, into) {
+                (Some(anchor), _, _) => MoveTarget::Before(anchor),
+                (_, Some(anchor), _) => MoveTarget::After(anchor),
+                (_, _, Some(parent)) => MoveTarget::Into(parent),
+                _ => {
+                    return Err(KugiriError::invalid(
+                        "Must specify --before, --after or --into for move command",
+                    ))
+                }
+            };
+            let text = read_file(&file)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&file)?);
+            let result = move_section(&doc, &id, &target)?;
+            output.emit(&file, &text, &result, cli.color)?;
+        }
+        Commands::Rename {
+            file,
+            id,
+            to,
+            insert_markers,
+            all_files,
+            root,
+            output,
+        } => {
+            if all_files && output.to_stdout() {
+                return Err(KugiriError::invalid(
+                    "--all-files can change several files; pass -w, --diff, --check or --patch",
+                ));
+            }
+            let text = read_file(&file)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&file)?);
+            let result = rename_section(&doc, &id, &to, insert_markers)?;
+            let mut changes = vec![FileChange {
+                path: file.clone(),
+                original: text,
+                result,
+            }];
+            if all_files {
+                let root = include_options(&file, root).root;
+                let (references, warnings) =
+                    rename_references(&file, &id, &to, &root, |path| cli.markers.syntax_for(path))?;
+                for warning in warnings {
+                    eprintln!("warning: {warning}");
+                }
+                changes.extend(references);
+            }
+            output.emit_all(&changes, cli.color)?;
+        }
+        Commands::Copy {
+            from,
+            to,
+            create_before,
+            create_after,
+            output,
+        } => {
+            let from_text = read_file(&from.path)?;
+            let from_doc = Document::parse(&from_text, &cli.markers.syntax_for(&from.path)?);
+            let text = read_file(&to.path)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&to.path)?);
+            let result = copy_section(
+                &from_doc,
+                &from.id,
+                &doc,
+                &to.id,
+                create_before.as_deref(),
+                create_after.as_deref(),
+            )?;
+            output.emit(&to.path, &text, &result, cli.color)?;
+        }
+        Commands::Check { files, fix, output } => {
+            if !fix && !output.to_stdout() {
+                return Err(KugiriError::invalid(
+                    "-w, --diff, --check and --patch only apply with --fix",
+                ));
+            }
+            if fix && files.len() > 1 && output.to_stdout() {
+                return Err(KugiriError::invalid(
+                    "check --fix with several files needs -w, --diff, --check or --patch",
+                ));
+            }
+            let color = cli.color.enabled(std::io::stderr());
+            let mut problems = 0;
+            let mut changes = Vec::new();
+            for file in &files {
+                let syntax = cli.markers.syntax_for(file)?;
+                let original = read_file(file)?;
+                let mut text = original.clone();
+                if fix {
+                    let (fixed, fixes) = fix_document(&Document::parse(&text, &syntax));
+                    if fixes > 0 && output.write {
+                        eprintln!("Fixed {fixes} problem(s) in {file}");
+                    }
+                    text = fixed;
+                }
+                let doc = Document::parse(&text, &syntax);
+                for diagnostic in check_document(&doc, file) {
+                    eprintln!("{}", diagnostic.render(doc.lines(), color));
+                    if diagnostic.severity == Severity::Error {
+                        problems += 1;
+                    }
+                }
+                if fix {
+                    changes.push(FileChange {
+                        path: file.clone(),
+                        original,
+                        result: text,
+                    });
+                }
+            }
+            output.emit_all(&changes, cli.color)?;
+            if problems > 0 {
+                return Err(KugiriError::Lint { problems });
+            }
+        }
+        Commands::Fmt { files, output } => {
+            if files.len() > 1 && output.to_stdout() {
+                return Err(KugiriError::invalid(
+                    "fmt with several files needs -w, --diff, --check or --patch",
+                ));
+            }
+            let mut changes = Vec::new();
+            for file in files {
+                let original = read_file(&file)?;
+                let result = format_markers(&original, &cli.markers.syntax_for(&file)?);
+                changes.push(FileChange {
+                    path: file,
+                    original,
+                    result,
+                });
+            }
+            output.emit_all(&changes, cli.color)?;
+        }
+        Commands::List {
+            file,
+            tree,
+            format,
+            select,
+        } => {
+            let text = read_file(&file)?;
+            let doc = Document::parse(&text, &cli.markers.syntax_for(&file)?);
+            let entries = if tree {
+                list_tree(&doc, select.as_ref())
             } else {
-                fs::read_to_string(&file)?
+                list_sections(&doc, select.as_ref())
+            };
+            let output = match format {
+                ListFormat::Json => format_json(&entries),
+                ListFormat::Text if tree => format_tree(&entries),
+                ListFormat::Text => format_list(&entries),
+            };
+            print!("{output}");
+        }
+        Commands::Trim { file, select } => {
+            let text = read_file_or_stdin(Some(&file))?;
+            let syntax = cli.markers.syntax_for(&file)?;
+            let result = match select {
+                Some(selector) => trim_selected(&text, &selector, &syntax)?,
+                None => trim_with(&text, &syntax),
             };
-            let result = trim(&text);
             println!("{result}");
         }
         Commands::Wrap { id, body_file } => {
             let content = read_file_or_stdin(body_file.as_deref())?;
-            let result = wrap(&content, &id);
+            let result = wrap_with(&content, &id, &cli.markers.syntax_for("-")?)?;
             println!("{result}");
         }
+        Commands::MigrateMarkers {
+            file,
+            to_style,
+            to_prefix,
+            to_begin_template,
+            to_end_template,
+            output,
+        } => {
+            if to_style.is_none() && to_prefix.is_none() && to_begin_template.is_none() {
+                return Err(KugiriError::invalid(
+                    "Specify --to-style, --to-prefix or --to-begin-template",
+                ));
+            }
+            let text = read_file(&file)?;
+            let from = cli.markers.syntax_for(&file)?;
+            // A new style or prefix starts over from the generated templates
+            let mut to = from.clone();
+            if to_style.is_some() || to_prefix.is_some() {
+                to = from
+                    .clone()
+                    .with_style(to_style.unwrap_or(from.style()))
+                    .with_prefix(to_prefix.as_deref().unwrap_or(from.prefix()));
+            }
+            if let (Some(begin), Some(end)) = (to_begin_template, to_end_template) {
+                to = to.with_templates(begin, end, None)?;
+            }
+            let result = migrate_markers(&text, &from, &to);
+            output.emit(&file, &text, &result, cli.color)?;
+        }
+        Commands::Convert { file, from, output } => {
+            let text = read_file(&file)?;
+            let result = convert(&text, from, &cli.markers.syntax_for(&file)?)?;
+            output.emit(&file, &text, &result, cli.color)?;
+        }
     }
 
     Ok(())
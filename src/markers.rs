@@ -1,3 +1,12 @@
+use crate::attributes::Attributes;
+use crate::document::Document;
+use crate::error::{KugiriError, Result};
+use crate::style::{is_markdown_path, MarkerStyle};
+use crate::template::{split_id, MarkerTemplate, ID_PLACEHOLDER};
+use std::ops::Range;
+
+pub const DEFAULT_PREFIX: &str = "KUGIRI";
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Section {
     pub id: String,
@@ -5,100 +14,346 @@ pub struct Section {
     pub end_line: usize,
     pub content: String,
     pub indent: String,
+    /// Set for inline sections, whose markers share a single line.
+    pub inline: Option<InlineSpan>,
+    /// `key=value` attributes written after the id in the BEGIN marker.
+    pub attributes: Attributes,
 }
 
-pub fn find_section(text: &str, id: &str) -> Option<Section> {
-    let begin_marker = make_begin_marker(id);
-    let end_marker = make_end_marker(id);
+impl Section {
+    /// Whether every `key=value` filter matches one of the section's attributes.
+    pub fn matches_attributes(&self, filters: &[(String, String)]) -> bool {
+        filters
+            .iter()
+            .all(|(key, value)| self.attributes.get(key) == Some(value))
+    }
+}
+
+/// Byte offsets of an inline section within its line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineSpan {
+    /// From the start of the BEGIN marker to the end of the END marker
+    pub outer: Range<usize>,
+    /// The content between the two markers
+    pub inner: Range<usize>,
+}
+
+/// A marker recognised on a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarkerLine<'a> {
+    pub kind: MarkerKind,
+    pub id: &'a str,
+    /// Raw attribute text after the id, empty when there is none.
+    pub attributes: &'a str,
+}
 
-    let lines: Vec<&str> = text.lines().collect();
+impl<'a> MarkerLine<'a> {
+    fn from_capture(kind: MarkerKind, captured: &'a str) -> Self {
+        let (id, attributes) = split_id(captured);
+        MarkerLine {
+            kind,
+            id,
+            attributes,
+        }
+    }
+}
 
-    let mut begin_line = None;
-    let mut end_line = None;
-    let mut indent = String::new();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Begin,
+    End,
+    Insert,
+}
 
-    for (idx, line) in lines.iter().enumerate() {
+impl MarkerKind {
+    pub const ALL: [MarkerKind; 3] = [MarkerKind::Begin, MarkerKind::End, MarkerKind::Insert];
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            MarkerKind::Begin => "BEGIN",
+            MarkerKind::End => "END",
+            MarkerKind::Insert => "INSERT",
+        }
+    }
+}
+
+/// Describes how markers are spelled in a particular file.
+///
+/// The BEGIN/END/INSERT templates are derived from the comment style and the
+/// prefix unless custom templates are set with [`MarkerSyntax::with_templates`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerSyntax {
+    style: MarkerStyle,
+    prefix: String,
+    begin: MarkerTemplate,
+    end: MarkerTemplate,
+    insert: MarkerTemplate,
+    markdown: bool,
+}
+
+impl Default for MarkerSyntax {
+    fn default() -> Self {
+        MarkerSyntax::new(MarkerStyle::default())
+    }
+}
+
+impl MarkerSyntax {
+    pub fn new(style: MarkerStyle) -> Self {
+        let template = |kind| style_template(style, DEFAULT_PREFIX, kind);
+        MarkerSyntax {
+            style,
+            prefix: DEFAULT_PREFIX.to_string(),
+            begin: template(MarkerKind::Begin),
+            end: template(MarkerKind::End),
+            insert: template(MarkerKind::Insert),
+            markdown: false,
+        }
+    }
+
+    /// Syntax for a file, detected from its extension.
+    pub fn for_path(path: &str) -> Self {
+        MarkerSyntax::new(MarkerStyle::from_path(path)).with_markdown(is_markdown_path(path))
+    }
+
+    pub fn with_style(self, style: MarkerStyle) -> Self {
+        MarkerSyntax::new(style)
+            .with_prefix(&self.prefix)
+            .with_markdown(self.markdown)
+    }
+
+    /// Treat the file as Markdown, so markers inside code blocks are ignored.
+    pub fn with_markdown(self, markdown: bool) -> Self {
+        MarkerSyntax { markdown, ..self }
+    }
+
+    pub fn with_prefix(self, prefix: &str) -> Self {
+        let template = |kind| style_template(self.style, prefix, kind);
+        MarkerSyntax {
+            prefix: prefix.to_string(),
+            begin: template(MarkerKind::Begin),
+            end: template(MarkerKind::End),
+            insert: template(MarkerKind::Insert),
+            ..self
+        }
+    }
+
+    /// Replace the marker templates; INSERT markers keep the style-derived
+    /// template unless `insert` is given.
+    pub fn with_templates(
+        self,
+        begin: MarkerTemplate,
+        end: MarkerTemplate,
+        insert: Option<MarkerTemplate>,
+    ) -> Result<Self> {
+        if begin == end {
+            return Err(KugiriError::invalid(
+                "BEGIN and END marker templates must differ",
+            ));
+        }
+        Ok(MarkerSyntax {
+            begin,
+            end,
+            insert: insert.unwrap_or(self.insert.clone()),
+            ..self
+        })
+    }
+
+    pub fn style(&self) -> MarkerStyle {
+        self.style
+    }
+
+    pub fn is_markdown(&self) -> bool {
+        self.markdown
+    }
+
+    /// A pragma line such as `<!-- KUGIRI-OFF -->`.
+    pub fn pragma(&self, name: &str) -> String {
+        self.style.comment(&format!("{}-{name}", self.prefix))
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn template(&self, kind: MarkerKind) -> &MarkerTemplate {
+        match kind {
+            MarkerKind::Begin => &self.begin,
+            MarkerKind::End => &self.end,
+            MarkerKind::Insert => &self.insert,
+        }
+    }
+
+    pub fn marker(&self, kind: MarkerKind, id: &str) -> String {
+        self.template(kind).render(id)
+    }
+
+    pub fn begin_marker(&self, id: &str) -> String {
+        self.marker(MarkerKind::Begin, id)
+    }
+
+    pub fn end_marker(&self, id: &str) -> String {
+        self.marker(MarkerKind::End, id)
+    }
+
+    pub fn insert_marker(&self, id: &str) -> String {
+        self.marker(MarkerKind::Insert, id)
+    }
+
+    /// Render a marker with raw attribute text after the id.
+    pub fn marker_with_attributes(&self, kind: MarkerKind, id: &str, attributes: &str) -> String {
+        if attributes.is_empty() {
+            self.marker(kind, id)
+        } else {
+            self.marker(kind, &format!("{id} {attributes}"))
+        }
+    }
+
+    /// Parse a marker line (leading whitespace allowed).
+    pub fn parse_marker<'a>(&self, line: &'a str) -> Option<MarkerLine<'a>> {
+        let trimmed = line.trim_start();
+        MarkerKind::ALL.into_iter().find_map(|kind| {
+            let captured = self.template(kind).capture(trimmed)?;
+            Some(MarkerLine::from_capture(kind, captured))
+        })
+    }
+
+    /// Like [`MarkerSyntax::parse_marker`], but tolerating spacing that
+    /// differs from the templates, as in `<!--KUGIRI-BEGIN:x-->`.
+    pub fn parse_marker_loose<'a>(&self, line: &'a str) -> Option<MarkerLine<'a>> {
         let trimmed = line.trim_start();
-        let line_indent = &line[..line.len() - trimmed.len()];
-
-        if trimmed == begin_marker {
-            begin_line = Some(idx);
-            indent = line_indent.to_string();
-        } else if trimmed == end_marker && begin_line.is_some() {
-            end_line = Some(idx);
-            break;
+        MarkerKind::ALL.into_iter().find_map(|kind| {
+            let captured = self.template(kind).capture_loose(trimmed)?;
+            Some(MarkerLine::from_capture(kind, captured))
+        })
+    }
+
+    /// Check that `id` can be written into a marker without breaking it.
+    pub fn validate_id(&self, id: &str) -> Result<()> {
+        if id.is_empty() {
+            return Err(KugiriError::invalid("Section id must not be empty"));
+        }
+        if id.contains(char::is_whitespace) {
+            return Err(KugiriError::invalid(format!(
+                "Invalid section id '{id}': ids must not contain whitespace"
+            )));
+        }
+        for kind in MarkerKind::ALL {
+            if let Some(delimiter) = self.template(kind).delimiters().find(|d| id.contains(d)) {
+                return Err(KugiriError::invalid(format!("Invalid section id '{id}': ids must not contain the marker delimiter '{delimiter}'")));
+            }
         }
+        Ok(())
+    }
+}
+
+fn style_template(style: MarkerStyle, prefix: &str, kind: MarkerKind) -> MarkerTemplate {
+    let body = format!("{prefix}-{}: {ID_PLACEHOLDER}", kind.keyword());
+    MarkerTemplate::parse(&style.comment(&body)).expect("style templates contain {id}")
+}
+
+/// Check that a marker prefix is usable, e.g. `KUGIRI` or `DOCGEN`.
+pub fn validate_prefix(prefix: &str) -> Result<()> {
+    if prefix.is_empty() {
+        return Err(KugiriError::invalid("Marker prefix must not be empty"));
+    }
+    if !prefix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(KugiriError::invalid(format!(
+            "Invalid marker prefix '{prefix}': use letters, digits, '_' or '-'"
+        )));
+    }
+    Ok(())
+}
+
+/// Find the inline sections in `line`, i.e. BEGIN and END markers for the
+/// same id embedded in the text, such as
+/// `Version: <!-- KUGIRI-BEGIN: ver -->1.2.3<!-- KUGIRI-END: ver -->`.
+pub fn find_inline_sections<'a>(
+    line: &'a str,
+    syntax: &MarkerSyntax,
+) -> Vec<(MarkerLine<'a>, InlineSpan)> {
+    let mut found = Vec::new();
+    if syntax.parse_marker(line).is_some() {
+        return found;
     }
 
-    match (begin_line, end_line) {
-        (Some(start), Some(end)) if start < end => {
-            let content_lines = &lines[start + 1..end];
-
-            // Remove the common indent from content lines
-            let content = content_lines
-                .iter()
-                .map(|line| {
-                    if line.starts_with(&indent) {
-                        &line[indent.len()..]
-                    } else {
-                        line
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            Some(Section {
-                id: id.to_string(),
-                start_line: start,
-                end_line: end,
-                content,
-                indent,
-            })
+    let begin = syntax.template(MarkerKind::Begin);
+    let end = syntax.template(MarkerKind::End);
+    let mut pos = 0;
+
+    while let Some((begin_range, captured)) = begin.find_in(line, pos) {
+        let marker = MarkerLine::from_capture(MarkerKind::Begin, captured);
+
+        // Look for the END marker with the same id further along the line
+        let mut search = begin_range.end;
+        let mut end_range = None;
+        while let Some((range, end_captured)) = end.find_in(line, search) {
+            if split_id(end_captured).0 == marker.id {
+                end_range = Some(range);
+                break;
+            }
+            search = range.end;
+        }
+
+        match end_range {
+            Some(end_range) => {
+                pos = end_range.end;
+                found.push((
+                    marker,
+                    InlineSpan {
+                        outer: begin_range.start..end_range.end,
+                        inner: begin_range.end..end_range.start,
+                    },
+                ));
+            }
+            None => pos = begin_range.end,
         }
-        _ => None,
     }
+
+    found
+}
+
+pub fn find_section(text: &str, id: &str) -> Option<Section> {
+    find_section_with(text, id, &MarkerSyntax::default())
+}
+
+pub fn find_section_with(text: &str, id: &str, syntax: &MarkerSyntax) -> Option<Section> {
+    Document::parse(text, syntax)
+        .get(id)
+        .map(|node| node.section.clone())
+}
+
+/// All sections in the file, in the order their BEGIN markers appear.
+pub fn find_sections_with(text: &str, syntax: &MarkerSyntax) -> Vec<Section> {
+    Document::parse(text, syntax)
+        .sections()
+        .map(|node| node.section.clone())
+        .collect()
 }
 
 pub fn make_begin_marker(id: &str) -> String {
-    format!("<!-- KUGIRI-BEGIN: {id} -->")
+    MarkerSyntax::default().begin_marker(id)
 }
 
 pub fn make_end_marker(id: &str) -> String {
-    format!("<!-- KUGIRI-END: {id} -->")
+    MarkerSyntax::default().end_marker(id)
 }
 
 pub fn make_insert_marker(id: &str) -> String {
-    format!("<!-- KUGIRI-INSERT: {id} -->")
+    MarkerSyntax::default().insert_marker(id)
 }
 
 // Find any type of marker (BEGIN, END, or INSERT) to use as an anchor
 pub fn find_marker_for_anchor(text: &str, id: &str) -> Option<Section> {
-    // First try to find a regular section
-    if let Some(section) = find_section(text, id) {
-        return Some(section);
-    }
-
-    // If not found, look for an INSERT marker
-    let insert_marker = make_insert_marker(id);
-    let lines: Vec<&str> = text.lines().collect();
-
-    for (idx, line) in lines.iter().enumerate() {
-        let trimmed = line.trim_start();
-        let line_indent = &line[..line.len() - trimmed.len()];
-
-        if trimmed == insert_marker {
-            // Found an INSERT marker, create a pseudo-section
-            return Some(Section {
-                id: id.to_string(),
-                start_line: idx,
-                end_line: idx, // For INSERT markers, start and end are the same
-                content: String::new(), // No content for INSERT markers
-                indent: line_indent.to_string(),
-            });
-        }
-    }
+    find_marker_for_anchor_with(text, id, &MarkerSyntax::default())
+}
 
-    None
+pub fn find_marker_for_anchor_with(text: &str, id: &str, syntax: &MarkerSyntax) -> Option<Section> {
+    Document::parse(text, syntax)
+        .anchor(id)
+        .map(|node| node.section.clone())
 }
 
 #[cfg(test)]
@@ -170,6 +425,246 @@ More content"#;
         assert_eq!(make_insert_marker("test"), "<!-- KUGIRI-INSERT: test -->");
     }
 
+    #[test]
+    fn test_find_section_with_style() {
+        let text = r#"fn main() {
+    // KUGIRI-BEGIN: body
+    println!("hi");
+    // KUGIRI-END: body
+}"#;
+
+        let syntax = MarkerSyntax::new(MarkerStyle::Slash);
+        let section = find_section_with(text, "body", &syntax).expect("Section should be found");
+        assert_eq!(section.content, "println!(\"hi\");");
+        assert_eq!(section.indent, "    ");
+
+        // HTML markers are not recognised in a slash-styled file and vice versa
+        assert!(find_section(text, "body").is_none());
+    }
+
+    #[test]
+    fn test_marker_syntax() {
+        let css = MarkerSyntax::new(MarkerStyle::Block);
+        assert_eq!(css.begin_marker("a"), "/* KUGIRI-BEGIN: a */");
+        assert_eq!(css.end_marker("a"), "/* KUGIRI-END: a */");
+
+        let rst = MarkerSyntax::for_path("index.rst");
+        assert_eq!(rst.insert_marker("a"), ".. KUGIRI-INSERT: a");
+    }
+
+    #[test]
+    fn test_find_section_with_prefix() {
+        let text = r#"<!-- KUGIRI-BEGIN: shared -->
+kugiri content
+<!-- KUGIRI-END: shared -->
+<!-- DOCGEN-BEGIN: shared -->
+docgen content
+<!-- DOCGEN-END: shared -->"#;
+
+        let docgen = MarkerSyntax::default().with_prefix("DOCGEN");
+        assert_eq!(docgen.begin_marker("a"), "<!-- DOCGEN-BEGIN: a -->");
+
+        let section = find_section_with(text, "shared", &docgen).expect("Section should be found");
+        assert_eq!(section.content, "docgen content");
+        assert_eq!(section.start_line, 3);
+
+        let section = find_section(text, "shared").expect("Section should be found");
+        assert_eq!(section.content, "kugiri content");
+    }
+
+    #[test]
+    fn test_parse_marker() {
+        let html = MarkerSyntax::default();
+        let marker = html.parse_marker("  <!-- KUGIRI-BEGIN: a.b -->").unwrap();
+        assert_eq!(marker.kind, MarkerKind::Begin);
+        assert_eq!(marker.id, "a.b");
+        assert_eq!(marker.attributes, "");
+
+        let marker = html.parse_marker("<!-- KUGIRI-INSERT: top -->").unwrap();
+        assert_eq!(marker.kind, MarkerKind::Insert);
+        assert_eq!(marker.id, "top");
+
+        let marker = html
+            .parse_marker("<!-- KUGIRI-BEGIN: api lang=rust owner=docs -->")
+            .unwrap();
+        assert_eq!(marker.id, "api");
+        assert_eq!(marker.attributes, "lang=rust owner=docs");
+        assert_eq!(html.parse_marker("<!-- KUGIRI-END: a"), None);
+        assert_eq!(html.parse_marker("<!-- KUGIRI-END:  -->"), None);
+        assert_eq!(html.parse_marker("<!-- OTHER-END: a -->"), None);
+
+        let hash = MarkerSyntax::new(MarkerStyle::Hash).with_prefix("GEN");
+        let marker = hash.parse_marker("# GEN-END: deps").unwrap();
+        assert_eq!((marker.kind, marker.id), (MarkerKind::End, "deps"));
+        assert_eq!(hash.parse_marker("# KUGIRI-END: deps"), None);
+    }
+
+    #[test]
+    fn test_find_section_with_templates() {
+        let text = r#"[tool]
+# >>> managed >>>
+key = "value"
+# <<< managed <<<
+"#;
+
+        let syntax = MarkerSyntax::new(MarkerStyle::Hash)
+            .with_templates(
+                "# >>> {id} >>>".parse().unwrap(),
+                "# <<< {id} <<<".parse().unwrap(),
+                None,
+            )
+            .unwrap();
+        let section = find_section_with(text, "managed", &syntax).expect("Section should be found");
+        assert_eq!(section.start_line, 1);
+        assert_eq!(section.end_line, 3);
+        assert_eq!(section.content, "key = \"value\"");
+
+        assert_eq!(syntax.insert_marker("top"), "# KUGIRI-INSERT: top");
+    }
+
+    #[test]
+    fn test_with_templates_rejects_identical() {
+        let template: MarkerTemplate = "## {id}".parse().unwrap();
+        let result = MarkerSyntax::default().with_templates(template.clone(), template, None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validate_id() {
+        let syntax = MarkerSyntax::default();
+        assert!(syntax.validate_id("v1.2.3").is_ok());
+        assert!(syntax.validate_id("").is_err());
+        assert!(syntax.validate_id("a b").is_err());
+
+        let err = syntax.validate_id("a-->").unwrap_err();
+        assert!(err.to_string().contains("'-->'"));
+
+        let syntax = MarkerSyntax::new(MarkerStyle::Hash)
+            .with_templates(
+                "# >>> {id} >>>".parse().unwrap(),
+                "# <<< {id} <<<".parse().unwrap(),
+                None,
+            )
+            .unwrap();
+        let err = syntax.validate_id("x>>>y").unwrap_err();
+        assert!(err.to_string().contains("marker delimiter '>>>'"));
+    }
+
+    #[test]
+    fn test_find_section_with_attributes() {
+        let text = r#"<!-- KUGIRI-BEGIN: api-table lang=rust generated=true owner="docs team" -->
+| fn | desc |
+<!-- KUGIRI-END: api-table -->"#;
+
+        let section = find_section(text, "api-table").expect("Section should be found");
+        assert_eq!(section.content, "| fn | desc |");
+        assert_eq!(section.attributes["lang"], "rust");
+        assert_eq!(section.attributes["generated"], "true");
+        assert_eq!(section.attributes["owner"], "docs team");
+
+        assert!(section.matches_attributes(&[("lang".to_string(), "rust".to_string())]));
+        assert!(!section.matches_attributes(&[("lang".to_string(), "go".to_string())]));
+
+        let inline = "v<!-- KUGIRI-BEGIN: ver channel=stable -->1.0<!-- KUGIRI-END: ver -->";
+        let section = find_section(inline, "ver").expect("Section should be found");
+        assert_eq!(section.content, "1.0");
+        assert_eq!(section.attributes["channel"], "stable");
+    }
+
+    #[test]
+    fn test_find_sections_with() {
+        let text = r#"<!-- KUGIRI-BEGIN: a -->
+<!-- KUGIRI-BEGIN: b -->
+<!-- KUGIRI-END: b -->
+<!-- KUGIRI-END: a -->
+<!-- KUGIRI-BEGIN: broken -->
+x <!-- KUGIRI-BEGIN: c -->y<!-- KUGIRI-END: c -->"#;
+
+        let sections = find_sections_with(text, &MarkerSyntax::default());
+        let ids: Vec<&str> = sections.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_find_section_skips_code_blocks_and_disabled_regions() {
+        let text = r#"Usage:
+
+```markdown
+<!-- KUGIRI-BEGIN: docs -->
+example
+<!-- KUGIRI-END: docs -->
+```
+
+<!-- KUGIRI-OFF -->
+<!-- KUGIRI-INSERT: anchor -->
+<!-- KUGIRI-ON -->
+
+<!-- KUGIRI-BEGIN: docs -->
+real
+<!-- KUGIRI-END: docs -->
+<!-- KUGIRI-INSERT: anchor -->"#;
+
+        let syntax = MarkerSyntax::for_path("README.md");
+        let section = find_section_with(text, "docs", &syntax).expect("Section should be found");
+        assert_eq!(section.content, "real");
+        assert_eq!(section.start_line, 12);
+
+        let anchor = find_marker_for_anchor_with(text, "anchor", &syntax).unwrap();
+        assert_eq!(anchor.start_line, 15);
+
+        // Without Markdown awareness the fenced example is found first
+        let plain = MarkerSyntax::default();
+        let section = find_section_with(text, "docs", &plain).expect("Section should be found");
+        assert_eq!(section.content, "example");
+    }
+
+    #[test]
+    fn test_find_inline_section() {
+        let text = r#"# Project
+
+Current version: <!-- KUGIRI-BEGIN: ver -->1.2.3<!-- KUGIRI-END: ver --> (stable)
+"#;
+
+        let section = find_section(text, "ver").expect("Section should be found");
+        assert_eq!(section.start_line, 2);
+        assert_eq!(section.end_line, 2);
+        assert_eq!(section.content, "1.2.3");
+
+        let span = section.inline.expect("Section should be inline");
+        let line = text.lines().nth(2).unwrap();
+        assert_eq!(&line[span.inner], "1.2.3");
+        assert_eq!(
+            &line[span.outer],
+            "<!-- KUGIRI-BEGIN: ver -->1.2.3<!-- KUGIRI-END: ver -->"
+        );
+    }
+
+    #[test]
+    fn test_find_inline_sections_multiple() {
+        let line = "[<!-- KUGIRI-BEGIN: a -->x<!-- KUGIRI-END: a -->] \
+                    [<!-- KUGIRI-BEGIN: b --><!-- KUGIRI-END: b -->] \
+                    [<!-- KUGIRI-BEGIN: c -->unclosed]";
+
+        let found = find_inline_sections(line, &MarkerSyntax::default());
+        let ids: Vec<&str> = found.iter().map(|(marker, _)| marker.id).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert!(found[1].1.inner.is_empty());
+
+        // Line comment styles have no inline form
+        let slash = MarkerSyntax::new(MarkerStyle::Slash);
+        assert!(find_inline_sections("x // KUGIRI-BEGIN: a y // KUGIRI-END: a", &slash).is_empty());
+    }
+
+    #[test]
+    fn test_validate_prefix() {
+        assert!(validate_prefix("KUGIRI").is_ok());
+        assert!(validate_prefix("DOC_GEN2").is_ok());
+        assert!(validate_prefix("").is_err());
+        assert!(validate_prefix("DOC GEN").is_err());
+        assert!(validate_prefix("DOC-GEN").is_ok());
+        assert!(validate_prefix("DOC:GEN").is_err());
+    }
+
     #[test]
     fn test_nested_markers() {
         let text = r#"# Document
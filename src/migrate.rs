@@ -0,0 +1,119 @@
+use crate::document::{Document, NodeKind};
+use crate::markers::{MarkerLine, MarkerSyntax};
+use crate::scanner::LineContext;
+use crate::utils::join_lines_preserve_trailing_newline;
+
+/// Rewrite every marker written in `from` syntax into `to` syntax, including
+/// the markers of inline sections.
+///
+/// Indentation and section content are left untouched; lines that are not
+/// markers in the `from` syntax are copied as-is.
+pub fn migrate_markers(text: &str, from: &MarkerSyntax, to: &MarkerSyntax) -> String {
+    let doc = Document::parse(text, from);
+    let migrate =
+        |marker: MarkerLine| to.marker_with_attributes(marker.kind, marker.id, marker.attributes);
+    let mut inline = doc
+        .sections()
+        .filter(|node| node.kind == NodeKind::Inline)
+        .peekable();
+    let mut result: Vec<String> = Vec::new();
+
+    for (idx, &line) in doc.lines().iter().enumerate() {
+        if doc.line_context(idx) != LineContext::Active {
+            result.push(line.to_string());
+            continue;
+        }
+
+        let mut migrated = String::new();
+        let mut pos = 0;
+        while let Some(span) = inline
+            .next_if(|node| node.section.start_line == idx)
+            .and_then(|node| node.section.inline.as_ref())
+        {
+            let begin = &line[span.outer.start..span.inner.start];
+            let end = &line[span.inner.end..span.outer.end];
+            migrated.push_str(&line[pos..span.outer.start]);
+            migrated.push_str(&from.parse_marker(begin).map_or(begin.to_string(), migrate));
+            migrated.push_str(&line[span.inner.clone()]);
+            migrated.push_str(&from.parse_marker(end).map_or(end.to_string(), migrate));
+            pos = span.outer.end;
+        }
+        if pos > 0 {
+            migrated.push_str(&line[pos..]);
+            result.push(migrated);
+            continue;
+        }
+
+        match from.parse_marker(line) {
+            Some(marker) => {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                result.push(format!("{indent}{}", migrate(marker)));
+            }
+            None => result.push(line.to_string()),
+        }
+    }
+
+    join_lines_preserve_trailing_newline(result, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::MarkerStyle;
+
+    #[test]
+    fn test_migrate_prefix() {
+        let text = r#"# README
+
+<!-- KUGIRI-BEGIN: usage -->
+  <!-- KUGIRI-INSERT: top -->
+Usage text
+<!-- KUGIRI-END: usage -->
+"#;
+
+        let from = MarkerSyntax::default();
+        let to = MarkerSyntax::default().with_prefix("DOCGEN");
+        let result = migrate_markers(text, &from, &to);
+
+        assert_eq!(
+            result,
+            r#"# README
+
+<!-- DOCGEN-BEGIN: usage -->
+  <!-- DOCGEN-INSERT: top -->
+Usage text
+<!-- DOCGEN-END: usage -->
+"#
+        );
+    }
+
+    #[test]
+    fn test_migrate_inline_sections() {
+        let text = "Version: v<!-- KUGIRI-BEGIN: ver -->1<!-- KUGIRI-END: ver --> and <!-- KUGIRI-BEGIN: date x=1 -->today<!-- KUGIRI-END: date -->\n";
+
+        let from = MarkerSyntax::default();
+        let to = MarkerSyntax::default().with_prefix("DOCGEN");
+        let result = migrate_markers(text, &from, &to);
+
+        assert_eq!(
+            result,
+            "Version: v<!-- DOCGEN-BEGIN: ver -->1<!-- DOCGEN-END: ver --> and <!-- DOCGEN-BEGIN: date x=1 -->today<!-- DOCGEN-END: date -->\n"
+        );
+        let doc = Document::parse(&result, &to);
+        assert_eq!(doc.sections().count(), 2);
+    }
+
+    #[test]
+    fn test_migrate_style_leaves_other_markers() {
+        let text = "# KUGIRI-BEGIN: a lang=py\nx = 1\n# KUGIRI-END: a\n# OTHER-BEGIN: b";
+
+        let from = MarkerSyntax::new(MarkerStyle::Hash);
+        let to = MarkerSyntax::new(MarkerStyle::Slash);
+        let result = migrate_markers(text, &from, &to);
+
+        assert_eq!(
+            result,
+            "// KUGIRI-BEGIN: a lang=py\nx = 1\n// KUGIRI-END: a\n# OTHER-BEGIN: b"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::apply::FileChange;
+use crate::document::{Document, SectionNode};
+use crate::error::{KugiriError, Result};
+use crate::include::{common_indent, content_hash, dedent, Embedded, IncludeOptions, SnippetRange};
+use crate::markers::MarkerSyntax;
+use crate::update::{indent_content, set_attributes, update_document};
+use crate::utils::join_lines_preserve_trailing_newline;
+
+/// Write the content of section `id` in `doc` (the file at `path`) back to
+/// the file named by its `src` attribute; the reverse of `include`.
+///
+/// The `hash` recorded by the last `include` or `push` tells which side
+/// changed since: only the section may have, unless `force` is set. Returns
+/// the change of the source file, then that of `doc`, whose `hash` (and the
+/// line range in `src`, when the snippet grew or shrank) is updated.
+pub fn push_section(
+    doc: &Document,
+    path: &str,
+    id: &str,
+    options: &IncludeOptions,
+    force: bool,
+    syntax_for: impl Fn(&str) -> Result<MarkerSyntax>,
+) -> Result<Vec<FileChange>> {
+    let node = doc.require(id)?;
+    let embedded = Embedded::resolve(node, options)?;
+    let raw = embedded.snippet(node, &syntax_for)?;
+    let current = dedent(&raw);
+    let edited = dedent(&unfence(node));
+    let edited_hash = content_hash(&edited);
+
+    let mut source_result = embedded.text.clone();
+    let mut attributes = vec![(node, "hash", edited_hash.clone())];
+
+    if edited_hash != content_hash(&current) {
+        if !force {
+            check_unchanged_source(node, &embedded.path, &edited_hash, &current)?;
+        }
+        let indented = indent_content(edited.trim_end_matches('\n'), common_indent(&raw));
+        match &embedded.source.range {
+            SnippetRange::File => {
+                source_result = join_lines_preserve_trailing_newline(
+                    indented.lines().map(str::to_string).collect(),
+                    &embedded.text,
+                );
+            }
+            SnippetRange::Lines(start, end) => {
+                let count = indented.lines().count();
+                if count == 0 {
+                    return Err(KugiriError::invalid(format!(
+                        "Section '{id}' is empty, so it can't replace lines of {}",
+                        embedded.path
+                    )));
+                }
+                let mut lines: Vec<String> = embedded.text.lines().map(str::to_string).collect();
+                lines.splice(start - 1..*end, indented.lines().map(str::to_string));
+                source_result = join_lines_preserve_trailing_newline(lines, &embedded.text);
+
+                let new_end = start + count - 1;
+                if new_end != *end {
+                    let src = format!("{}#L{start}-L{new_end}", embedded.source.path);
+                    attributes.push((node, "src", src));
+                }
+            }
+            SnippetRange::Section(source_id) => {
+                let source_doc = Document::parse(&embedded.text, &syntax_for(&embedded.path)?);
+                let section = source_doc.require(source_id)?;
+                // Section content leaves out the markers' indent, which
+                // update adds back
+                let content = indent_content(edited.trim_end_matches('\n'), common_indent(&raw));
+                source_result = update_document(&source_doc, &[section], &content)?;
+            }
+        }
+    }
+
+    Ok(vec![
+        FileChange {
+            path: embedded.path,
+            original: embedded.text.clone(),
+            result: source_result,
+        },
+        FileChange {
+            path: path.to_string(),
+            original: doc.text().to_string(),
+            result: set_attributes(doc, &attributes),
+        },
+    ])
+}
+
+/// Refuse to overwrite changes made to the source since the last sync.
+fn check_unchanged_source(
+    node: &SectionNode,
+    source: &str,
+    edited_hash: &str,
+    current: &str,
+) -> Result<()> {
+    let id = node.id();
+    let Some(stored) = node.section.attributes.get("hash") else {
+        return Err(KugiriError::Conflict(format!(
+            "Section '{id}' has no hash attribute, so it is unknown whether {source} changed; run `kugiri include` first, or pass --force"
+        )));
+    };
+    if content_hash(current) == *stored {
+        return Ok(());
+    }
+    if edited_hash == stored {
+        return Err(KugiriError::Conflict(format!(
+            "{source} changed since the last sync and section '{id}' did not; run `kugiri include` to pull the change"
+        )));
+    }
+    Err(KugiriError::Conflict(format!(
+        "Both section '{id}' and {source} changed since the last sync; merge them by hand, or pass --force to overwrite {source}"
+    )))
+}
+
+/// The content of `node` without the code fence `include` wrapped it in.
+fn unfence(node: &SectionNode) -> String {
+    let content = &node.section.content;
+    if !node.section.attributes.contains_key("fence") {
+        return content.clone();
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    let is_fence = |line: &&str| line.trim_start().starts_with("```");
+    match lines.as_slice() {
+        [first, inner @ .., last] if is_fence(first) && is_fence(last) => inner.join("\n"),
+        _ => content.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::include::include_document;
+    use std::fs;
+    use std::path::Path;
+
+    const SOURCE: &str = "fn main() {\n    let a = 1;\n    let b = 2;\n}\n";
+
+    /// The README after `include`, its snippet edited with `edit`.
+    fn setup(dir: &Path, src: &str, edit: impl Fn(String) -> String) -> (String, IncludeOptions) {
+        fs::write(dir.join("main.rs"), SOURCE).unwrap();
+        let options = IncludeOptions {
+            base: dir.to_path_buf(),
+            root: dir.to_path_buf(),
+        };
+        let text = format!(
+            "<!-- KUGIRI-BEGIN: example src=\"{src}\" fence=rust -->\n<!-- KUGIRI-END: example -->\n"
+        );
+        let doc = Document::parse(&text, &MarkerSyntax::default());
+        let included = include_document(&doc, &options, syntax).unwrap();
+        (edit(included), options)
+    }
+
+    fn syntax(path: &str) -> Result<MarkerSyntax> {
+        Ok(MarkerSyntax::for_path(path))
+    }
+
+    fn push(text: &str, options: &IncludeOptions, force: bool) -> Result<Vec<FileChange>> {
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        push_section(&doc, "README.md", "example", options, force, syntax)
+    }
+
+    #[test]
+    fn test_push_line_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let (text, options) = setup(dir.path(), "main.rs#L2-L3", |text| {
+            text.replace("let b = 2;", "let b = 2;\nlet c = 3;")
+        });
+
+        let changes = push(&text, &options, false).unwrap();
+
+        assert_eq!(
+            changes[0].result,
+            "fn main() {\n    let a = 1;\n    let b = 2;\n    let c = 3;\n}\n"
+        );
+        assert!(changes[1].result.contains("src=\"main.rs#L2-L4\""));
+        assert!(changes[1]
+            .result
+            .contains(&content_hash("let a = 1;\nlet b = 2;\nlet c = 3;")));
+    }
+
+    #[test]
+    fn test_push_section_of_source() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("lib.rs"),
+            "mod a {\n    // KUGIRI-BEGIN: usage\n      use x;\n    // KUGIRI-END: usage\n}\n",
+        )
+        .unwrap();
+        let options = IncludeOptions {
+            base: dir.path().to_path_buf(),
+            root: dir.path().to_path_buf(),
+        };
+        let text =
+            "<!-- KUGIRI-BEGIN: example src=lib.rs#kugiri=usage -->\nuse y;\n<!-- KUGIRI-END: example -->\n";
+
+        let changes = push(text, &options, true).unwrap();
+
+        assert_eq!(
+            changes[0].result,
+            "mod a {\n    // KUGIRI-BEGIN: usage\n      use y;\n    // KUGIRI-END: usage\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_push_refuses_changed_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let (text, options) = setup(dir.path(), "main.rs", |text| {
+            text.replace("let a = 1;", "let a = 10;")
+        });
+        fs::write(
+            dir.path().join("main.rs"),
+            SOURCE.replace("let b", "let bb"),
+        )
+        .unwrap();
+
+        let err = push(&text, &options, false).unwrap_err();
+        assert_eq!(err.exit_code(), KugiriError::EXIT_FAILURE);
+        assert!(err.to_string().starts_with("Both section 'example' and"));
+
+        // Only the source changed: pushing would revert it
+        let (text, _) = setup(dir.path(), "main.rs", |text| text);
+        fs::write(
+            dir.path().join("main.rs"),
+            SOURCE.replace("let b", "let bb"),
+        )
+        .unwrap();
+        let err = push(&text, &options, false).unwrap_err();
+        assert!(err.to_string().contains("run `kugiri include` to pull"));
+
+        let changes = push(&text, &options, true).unwrap();
+        assert_eq!(changes[0].result, SOURCE);
+    }
+
+    #[test]
+    fn test_push_unchanged_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let (text, options) = setup(dir.path(), "main.rs#L2-L3", |text| text);
+
+        let changes = push(&text, &options, false).unwrap();
+        assert_eq!(changes[0].result, changes[0].original);
+        assert_eq!(changes[1].result, text);
+    }
+}
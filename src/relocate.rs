@@ -0,0 +1,241 @@
+use crate::document::{Document, NodeKind, SectionNode};
+use crate::error::{KugiriError, Result};
+use crate::utils::join_lines_preserve_trailing_newline;
+
+/// Where `move` puts a section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveTarget {
+    /// Before the section or INSERT marker with this id
+    Before(String),
+    /// After the section or INSERT marker with this id
+    After(String),
+    /// At the end of the content of this section
+    Into(String),
+}
+
+/// Cut section `id`, markers and attributes included, and reinsert it at
+/// `target`, re-indented for its new location.
+///
+/// Like `remove` followed by `insert`, the blank line separating the section
+/// from its neighbours moves with it.
+pub fn move_section(doc: &Document, id: &str, target: &MoveTarget) -> Result<String> {
+    let node = doc.require(id)?;
+    if node.kind == NodeKind::Inline {
+        return Err(KugiriError::invalid(format!(
+            "Inline section '{id}' can't be moved; only sections on their own lines can"
+        )));
+    }
+
+    let (anchor, at, indent) = match target {
+        MoveTarget::Before(anchor) => {
+            let anchor = doc.require_anchor(anchor)?;
+            (anchor, anchor.section.start_line, &anchor.section.indent)
+        }
+        MoveTarget::After(anchor) => {
+            let anchor = doc.require_anchor(anchor)?;
+            (anchor, anchor.section.end_line + 1, &anchor.section.indent)
+        }
+        MoveTarget::Into(parent) => {
+            let parent = doc.require(parent)?;
+            if parent.kind != NodeKind::Block {
+                return Err(KugiriError::invalid(format!(
+                    "Inline section '{}' can't hold other sections",
+                    parent.id()
+                )));
+            }
+            (parent, parent.section.end_line, &parent.section.indent)
+        }
+    };
+    if within(anchor, node) {
+        return Err(KugiriError::invalid(format!(
+            "Can't move section '{id}' relative to itself or a section inside it"
+        )));
+    }
+
+    let lines = doc.lines();
+    let (start, end) = (node.section.start_line, node.section.end_line);
+    // Take one separating blank line along, preferably the one after
+    let blank = |line: usize| lines.get(line).is_some_and(|line| line.trim().is_empty());
+    let cut = if blank(end + 1) {
+        start..end + 2
+    } else if start > 0 && blank(start - 1) {
+        start - 1..end + 1
+    } else {
+        start..end + 1
+    };
+
+    let moved: Vec<String> = lines[start..=end]
+        .iter()
+        .map(|line| {
+            let line = line
+                .strip_prefix(node.section.indent.as_str())
+                .unwrap_or(line.trim_start());
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{indent}{line}")
+            }
+        })
+        .collect();
+
+    // Separate the section from the line before it, except from the BEGIN
+    // marker of the section it moves into
+    let previous = (0..at).rev().find(|line| !cut.contains(line));
+    let separate = match target {
+        MoveTarget::Before(_) => false,
+        MoveTarget::After(_) => true,
+        MoveTarget::Into(_) => {
+            previous.is_some_and(|line| line != anchor.section.start_line && !blank(line))
+        }
+    };
+
+    let mut result: Vec<String> = Vec::new();
+    let place = |result: &mut Vec<String>| {
+        if separate {
+            result.push(String::new());
+        }
+        result.extend(moved.iter().cloned());
+        if let MoveTarget::Before(_) = target {
+            result.push(String::new());
+        }
+    };
+
+    for (idx, line) in lines.iter().enumerate() {
+        if idx == at {
+            place(&mut result);
+        }
+        if !cut.contains(&idx) {
+            result.push(line.to_string());
+        }
+    }
+    if at == lines.len() {
+        place(&mut result);
+    }
+
+    Ok(join_lines_preserve_trailing_newline(result, doc.text()))
+}
+
+/// Whether `node` is `ancestor` or nested inside it.
+fn within(node: &SectionNode, ancestor: &SectionNode) -> bool {
+    node.section.start_line >= ancestor.section.start_line
+        && node.section.end_line <= ancestor.section.end_line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markers::MarkerSyntax;
+
+    const CHANGELOG: &str = r#"# Changelog
+
+<!-- KUGIRI-BEGIN: v1.1.0 -->
+## 1.1.0
+<!-- KUGIRI-END: v1.1.0 -->
+
+<!-- KUGIRI-BEGIN: v1.0.0 date=2024-01-01 -->
+## 1.0.0
+<!-- KUGIRI-END: v1.0.0 -->
+
+Footer
+"#;
+
+    fn move_in(text: &str, id: &str, target: MoveTarget) -> Result<String> {
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        move_section(&doc, id, &target)
+    }
+
+    #[test]
+    fn test_move_before() {
+        assert_eq!(
+            move_in(
+                CHANGELOG,
+                "v1.0.0",
+                MoveTarget::Before("v1.1.0".to_string())
+            )
+            .unwrap(),
+            r#"# Changelog
+
+<!-- KUGIRI-BEGIN: v1.0.0 date=2024-01-01 -->
+## 1.0.0
+<!-- KUGIRI-END: v1.0.0 -->
+
+<!-- KUGIRI-BEGIN: v1.1.0 -->
+## 1.1.0
+<!-- KUGIRI-END: v1.1.0 -->
+
+Footer
+"#
+        );
+    }
+
+    #[test]
+    fn test_move_after() {
+        assert_eq!(
+            move_in(CHANGELOG, "v1.1.0", MoveTarget::After("v1.0.0".to_string())).unwrap(),
+            r#"# Changelog
+
+<!-- KUGIRI-BEGIN: v1.0.0 date=2024-01-01 -->
+## 1.0.0
+<!-- KUGIRI-END: v1.0.0 -->
+
+<!-- KUGIRI-BEGIN: v1.1.0 -->
+## 1.1.0
+<!-- KUGIRI-END: v1.1.0 -->
+
+Footer
+"#
+        );
+    }
+
+    #[test]
+    fn test_move_into_reindents() {
+        let text = r#"- guide
+  <!-- KUGIRI-BEGIN: guide -->
+  Intro
+  <!-- KUGIRI-END: guide -->
+
+<!-- KUGIRI-BEGIN: example -->
+```
+  code
+```
+<!-- KUGIRI-END: example -->
+"#;
+        assert_eq!(
+            move_in(text, "example", MoveTarget::Into("guide".to_string())).unwrap(),
+            r#"- guide
+  <!-- KUGIRI-BEGIN: guide -->
+  Intro
+
+  <!-- KUGIRI-BEGIN: example -->
+  ```
+    code
+  ```
+  <!-- KUGIRI-END: example -->
+  <!-- KUGIRI-END: guide -->
+"#
+        );
+    }
+
+    #[test]
+    fn test_move_errors() {
+        let target = |id: &str| MoveTarget::Into(id.to_string());
+        assert_eq!(
+            move_in(CHANGELOG, "v1.1.0", target("v1.1.0"))
+                .unwrap_err()
+                .exit_code(),
+            KugiriError::EXIT_INVALID
+        );
+        assert_eq!(
+            move_in(CHANGELOG, "v9", target("v1.1.0"))
+                .unwrap_err()
+                .exit_code(),
+            KugiriError::EXIT_NOT_FOUND
+        );
+        assert_eq!(
+            move_in(CHANGELOG, "v1.1.0", MoveTarget::After("nope".to_string()))
+                .unwrap_err()
+                .exit_code(),
+            KugiriError::EXIT_NOT_FOUND
+        );
+    }
+}
@@ -1,29 +1,104 @@
-use crate::markers::find_section;
+use crate::document::{outermost, Document, NodeKind, Occurrence, SectionNode};
+use crate::error::Result;
+use crate::markers::MarkerSyntax;
+use crate::selector::Selector;
 use crate::utils::join_lines_preserve_trailing_newline;
-use anyhow::Result;
 
 pub fn remove(text: &str, id: &str) -> Result<String> {
-    let section = find_section(text, id)
-        .ok_or_else(|| anyhow::anyhow!("Section with id '{id}' not found"))?;
+    remove_with(text, id, &MarkerSyntax::default())
+}
+
+pub fn remove_with(text: &str, id: &str, syntax: &MarkerSyntax) -> Result<String> {
+    remove_occurrences(text, id, Occurrence::First, syntax)
+}
 
-    let lines: Vec<&str> = text.lines().collect();
+/// Remove the sections with this id picked by `occurrence`.
+pub fn remove_occurrences(
+    text: &str,
+    id: &str,
+    occurrence: Occurrence,
+    syntax: &MarkerSyntax,
+) -> Result<String> {
+    syntax.validate_id(id)?;
+    let doc = Document::parse(text, syntax);
+    let nodes = doc.require_occurrences(id, occurrence)?;
+    Ok(remove_document(&doc, &nodes))
+}
+
+/// Remove the sections matched by `selector`, narrowed down by `occurrence`.
+pub fn remove_selected(
+    text: &str,
+    selector: &Selector,
+    occurrence: Occurrence,
+    syntax: &MarkerSyntax,
+) -> Result<String> {
+    let doc = Document::parse(text, syntax);
+    let nodes = occurrence.select(selector.select(&doc)?, &selector.to_string())?;
+    Ok(remove_document(&doc, &nodes))
+}
+
+/// Remove `nodes` and their markers from the document.
+pub fn remove_document(doc: &Document, nodes: &[&SectionNode]) -> String {
     let mut result: Vec<String> = Vec::new();
+    let mut targets = outermost(nodes).into_iter().peekable();
+    let mut skip_until = None;
+
+    for (idx, &line) in doc.lines().iter().enumerate() {
+        if let Some(end) = skip_until {
+            if idx <= end {
+                // Skip the entire section including markers
+                continue;
+            }
+            skip_until = None;
+        }
+
+        let mut here = Vec::new();
+        while let Some(node) = targets.next_if(|node| node.section.start_line == idx) {
+            here.push(node);
+        }
 
-    for (idx, &line) in lines.iter().enumerate() {
-        if idx >= section.start_line && idx <= section.end_line {
-            // Skip the entire section including markers
-            continue;
+        match here.first() {
+            Some(node) if node.kind == NodeKind::Block => {
+                skip_until = Some(node.section.end_line);
+            }
+            Some(_) => {
+                // Cut the markers and their content out of the line, dropping
+                // the line if nothing else is left on it
+                let mut rest = String::new();
+                let mut pos = 0;
+                for span in here.iter().filter_map(|node| node.section.inline.as_ref()) {
+                    rest.push_str(&line[pos..span.outer.start]);
+                    pos = span.outer.end;
+                }
+                rest.push_str(&line[pos..]);
+                if !rest.trim().is_empty() {
+                    result.push(rest);
+                }
+            }
+            None => result.push(line.to_string()),
         }
-        result.push(line.to_string());
     }
 
-    Ok(join_lines_preserve_trailing_newline(result, text))
+    join_lines_preserve_trailing_newline(result, doc.text())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_remove_occurrences() {
+        let text = "a<!-- KUGIRI-BEGIN: x -->1<!-- KUGIRI-END: x -->b<!-- KUGIRI-BEGIN: x -->2<!-- KUGIRI-END: x -->\n<!-- KUGIRI-BEGIN: x -->\n3\n<!-- KUGIRI-END: x -->\nend";
+        let syntax = MarkerSyntax::default();
+
+        let result = remove_occurrences(text, "x", Occurrence::All, &syntax).unwrap();
+        assert_eq!(result, "ab\nend");
+
+        let result = remove_occurrences(text, "x", Occurrence::Nth(3), &syntax).unwrap();
+        assert!(result.ends_with("-->\nend"));
+        assert!(!result.contains('3'));
+    }
+
     #[test]
     fn test_remove_section() {
         let text = r#"Header
@@ -43,6 +118,18 @@ Footer"#;
         assert!(!result.contains("KUGIRI-END: to-remove"));
     }
 
+    #[test]
+    fn test_remove_inline_section() {
+        let text = "Badges: <!-- KUGIRI-BEGIN: ci -->[CI](ci.svg)<!-- KUGIRI-END: ci --> [Docs](docs.svg)\n<!-- KUGIRI-BEGIN: solo -->x<!-- KUGIRI-END: solo -->\nEnd";
+
+        let result = remove(text, "ci").unwrap();
+        assert!(result.starts_with("Badges:  [Docs](docs.svg)\n"));
+
+        let result = remove(text, "solo").unwrap();
+        assert!(!result.contains("solo"));
+        assert!(result.ends_with("[Docs](docs.svg)\nEnd"));
+    }
+
     #[test]
     fn test_remove_section_not_found() {
         let text = "Some text";
@@ -0,0 +1,343 @@
+use crate::apply::{FileChange, Manifest, Operation};
+use crate::document::{Document, NodeKind};
+use crate::error::{KugiriError, Result};
+use crate::include::{SnippetRange, SnippetSource};
+use crate::io::read_file;
+use crate::markers::MarkerSyntax;
+use crate::update::set_attributes;
+use crate::utils::join_lines_preserve_trailing_newline;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Directories never searched for references.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+/// Rename section `old` to `new`, rewriting the BEGIN and END markers of
+/// every section with that id, and its INSERT markers too when `inserts` is
+/// set. Attributes and indentation are kept. An id already used by a section
+/// or INSERT marker is refused.
+pub fn rename_section(doc: &Document, old: &str, new: &str, inserts: bool) -> Result<String> {
+    let syntax = doc.syntax();
+    syntax.validate_id(new)?;
+    doc.require(old)?;
+    if let Some(existing) = doc.anchor(new) {
+        return Err(KugiriError::Duplicate(format!(
+            "Id '{new}' is already used at line {}",
+            existing.section.start_line + 1
+        )));
+    }
+
+    let lines = doc.lines();
+    let marker_span = |line: usize| {
+        let text = lines[line];
+        text.len() - text.trim_start().len()..text.trim_end().len()
+    };
+    let mut edits: Vec<(usize, Range<usize>)> = Vec::new();
+    for node in doc.nodes().iter().filter(|node| node.id() == old) {
+        let section = &node.section;
+        match (&section.inline, node.kind) {
+            (Some(span), _) => {
+                edits.push((section.start_line, span.outer.start..span.inner.start));
+                edits.push((section.start_line, span.inner.end..span.outer.end));
+            }
+            (None, NodeKind::Block) => {
+                edits.push((section.start_line, marker_span(section.start_line)));
+                edits.push((section.end_line, marker_span(section.end_line)));
+            }
+            (None, _) if inserts => {
+                edits.push((section.start_line, marker_span(section.start_line)));
+            }
+            (None, _) => {}
+        }
+    }
+    // Later markers first, so that earlier spans on the same line stay valid
+    edits.sort_by_key(|(line, span)| std::cmp::Reverse((*line, span.start)));
+
+    let mut result: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    for (line, span) in edits {
+        let text = &mut result[line];
+        if let Some(marker) = syntax.parse_marker(&text[span.clone()]) {
+            let renamed = syntax.marker_with_attributes(marker.kind, new, marker.attributes);
+            text.replace_range(span, &renamed);
+        }
+    }
+
+    Ok(join_lines_preserve_trailing_newline(result, doc.text()))
+}
+
+/// References to section `old` of the file at `path` found under `root`,
+/// updated to `new`:
+///
+/// - `src="...#kugiri=old"` attributes naming that file
+/// - `id`, `before` and `after` of `apply` manifest operations on that file
+///
+/// Returns the changed files and warnings about manifests left alone because
+/// they also use `old` for other files.
+pub fn rename_references(
+    path: &str,
+    old: &str,
+    new: &str,
+    root: &Path,
+    syntax_for: impl Fn(&str) -> Result<MarkerSyntax>,
+) -> Result<(Vec<FileChange>, Vec<String>)> {
+    let target = Path::new(path)
+        .canonicalize()
+        .map_err(|err| KugiriError::io(path, err))?;
+    let mut changes = Vec::new();
+    let mut warnings = Vec::new();
+
+    for file in text_files(root) {
+        if file.canonicalize().is_ok_and(|file| file == target) {
+            continue;
+        }
+        let file = file.to_string_lossy().into_owned();
+        let Ok(text) = read_file(&file) else {
+            continue;
+        };
+        let base = Path::new(&file).parent().unwrap_or(Path::new(""));
+
+        let result = if is_manifest(&file) {
+            match Manifest::parse(&text, &file) {
+                Ok(manifest) => {
+                    let refers =
+                        |name: &str| base.join(name).canonicalize().is_ok_and(|f| f == target);
+                    let mentions = |op: &Operation| {
+                        [Some(&op.id), op.before.as_ref(), op.after.as_ref()]
+                            .into_iter()
+                            .flatten()
+                            .any(|id| id == old)
+                    };
+                    let (ours, others): (Vec<_>, Vec<_>) = manifest
+                        .operations
+                        .iter()
+                        .filter(|op| mentions(op))
+                        .partition(|op| refers(&op.file));
+                    if ours.is_empty() {
+                        continue;
+                    }
+                    if !others.is_empty() {
+                        warnings.push(format!(
+                            "{file}: not updated, as '{old}' also names sections of other files"
+                        ));
+                        continue;
+                    }
+                    rename_manifest_values(&text, old, new)
+                }
+                Err(_) => continue,
+            }
+        } else {
+            let Ok(syntax) = syntax_for(&file) else {
+                continue;
+            };
+            let doc = Document::parse(&text, &syntax);
+            let sources: Vec<_> = doc
+                .sections()
+                .filter_map(|node| {
+                    let source = SnippetSource::parse(node.section.attributes.get("src")?).ok()?;
+                    let names_old = source.range == SnippetRange::Section(old.to_string());
+                    let names_target = base
+                        .join(&source.path)
+                        .canonicalize()
+                        .is_ok_and(|f| f == target);
+                    (names_old && names_target)
+                        .then(|| (node, "src", format!("{}#kugiri={new}", source.path)))
+                })
+                .collect();
+            if sources.is_empty() {
+                continue;
+            }
+            set_attributes(&doc, &sources)
+        };
+
+        if result != text {
+            changes.push(FileChange {
+                path: relative_to_cwd(&file),
+                original: text,
+                result,
+            });
+        }
+    }
+
+    Ok((changes, warnings))
+}
+
+/// `path` relative to the current directory when it is inside it, so that
+/// diffs and patches of files found under an absolute root stay usable.
+fn relative_to_cwd(path: &str) -> String {
+    let cwd = std::env::current_dir().and_then(|cwd| cwd.canonicalize());
+    match (cwd, Path::new(path).canonicalize()) {
+        (Ok(cwd), Ok(path)) if path.starts_with(&cwd) => path
+            .strip_prefix(&cwd)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+fn is_manifest(path: &str) -> bool {
+    [".yaml", ".yml", ".json"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+/// Files under `root` in a stable order, skipping hidden and build
+/// directories.
+fn text_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+            if kind.is_dir() {
+                if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                    dirs.push(entry.path());
+                }
+            } else if kind.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Replace `old` as the value of `id`, `before` or `after` keys in YAML or
+/// JSON text, quoted or not, keeping everything else as written.
+fn rename_manifest_values(text: &str, old: &str, new: &str) -> String {
+    let mut result = text.to_string();
+    for key in ["id", "before", "after"] {
+        for quote in ["", "\"", "'"] {
+            for key_text in [format!("{key}:"), format!("\"{key}\":")] {
+                let value = format!("{quote}{old}{quote}");
+                let mut from = 0;
+                while let Some(found) = result[from..].find(&key_text) {
+                    let key_start = from + found;
+                    from = key_start + key_text.len();
+                    let boundary = result[..key_start]
+                        .chars()
+                        .next_back()
+                        .is_none_or(|c| c.is_whitespace() || matches!(c, '{' | ',' | '-'));
+                    let rest = &result[from..];
+                    let spaces = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+                    let value_start = from + spaces;
+                    let value_end = value_start + value.len();
+                    let ends = result[value_start..].starts_with(&value)
+                        && result[value_end..]
+                            .chars()
+                            .next()
+                            .is_none_or(|c| c.is_whitespace() || matches!(c, ',' | '}' | '#'));
+                    if boundary && ends {
+                        result
+                            .replace_range(value_start..value_end, &format!("{quote}{new}{quote}"));
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn rename(text: &str, inserts: bool) -> Result<String> {
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        rename_section(&doc, "old", "new", inserts)
+    }
+
+    #[test]
+    fn test_rename_section() {
+        let text = "<!-- KUGIRI-INSERT: old -->\n  <!-- KUGIRI-BEGIN: old lang=rust -->\n  x\n  <!-- KUGIRI-END: old -->\nv<!-- KUGIRI-BEGIN: old -->1<!-- KUGIRI-END: old --> <!-- KUGIRI-BEGIN: b -->2<!-- KUGIRI-END: b -->\n";
+
+        assert_eq!(
+            rename(text, false).unwrap(),
+            "<!-- KUGIRI-INSERT: old -->\n  <!-- KUGIRI-BEGIN: new lang=rust -->\n  x\n  <!-- KUGIRI-END: new -->\nv<!-- KUGIRI-BEGIN: new -->1<!-- KUGIRI-END: new --> <!-- KUGIRI-BEGIN: b -->2<!-- KUGIRI-END: b -->\n"
+        );
+        assert!(rename(text, true)
+            .unwrap()
+            .starts_with("<!-- KUGIRI-INSERT: new -->\n"));
+    }
+
+    #[test]
+    fn test_rename_errors() {
+        let text =
+            "<!-- KUGIRI-BEGIN: old -->\n<!-- KUGIRI-END: old -->\n<!-- KUGIRI-INSERT: new -->\n";
+        assert_eq!(
+            rename(text, false).unwrap_err().exit_code(),
+            KugiriError::EXIT_DUPLICATE
+        );
+        assert_eq!(
+            rename("text\n", false).unwrap_err().exit_code(),
+            KugiriError::EXIT_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn test_rename_manifest_values() {
+        let yaml = "operations:\n  - file: a.md\n    op: update\n    id: old # comment\n  - {file: a.md, op: insert, id: x, after: 'old'}\n  - {file: a.md, op: remove, id: older}\n";
+        assert_eq!(
+            rename_manifest_values(yaml, "old", "new"),
+            "operations:\n  - file: a.md\n    op: update\n    id: new # comment\n  - {file: a.md, op: insert, id: x, after: 'new'}\n  - {file: a.md, op: remove, id: older}\n"
+        );
+
+        let json = r#"{"operations": [{"file": "a.md", "op": "remove", "id": "old"}]}"#;
+        assert_eq!(
+            rename_manifest_values(json, "old", "new"),
+            r#"{"operations": [{"file": "a.md", "op": "remove", "id": "new"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_rename_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(
+            root.join("lib.rs"),
+            "// KUGIRI-BEGIN: old\n// KUGIRI-END: old\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("docs/guide.md"),
+            "<!-- KUGIRI-BEGIN: usage src=\"../lib.rs#kugiri=old\" fence=rust -->\n<!-- KUGIRI-END: usage -->\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("kugiri.yaml"),
+            "operations:\n  - {file: lib.rs, op: update, id: old, body: x}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("other.yaml"),
+            "operations:\n  - {file: lib.rs, op: remove, id: old}\n  - {file: docs/guide.md, op: remove, id: old}\n",
+        )
+        .unwrap();
+
+        let lib = root.join("lib.rs").to_string_lossy().into_owned();
+        let (changes, warnings) = rename_references(&lib, "old", "new", root, |path| {
+            Ok(MarkerSyntax::for_path(path))
+        })
+        .unwrap();
+
+        assert_eq!(changes.len(), 2);
+        assert!(changes[0].path.ends_with("guide.md"));
+        assert!(changes[0]
+            .result
+            .contains("src=\"../lib.rs#kugiri=new\" fence=rust"));
+        assert!(changes[1].path.ends_with("kugiri.yaml"));
+        assert!(changes[1].result.contains("id: new"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("other.yaml"));
+    }
+}
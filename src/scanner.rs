@@ -0,0 +1,235 @@
+use crate::markers::{MarkerKind, MarkerSyntax};
+
+/// Whether markers on a line are recognised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineContext {
+    /// Markers on this line are recognised
+    Active,
+    /// Inside a fenced or indented Markdown code block
+    Code,
+    /// Between `KUGIRI-OFF` and `KUGIRI-ON` pragmas
+    Disabled,
+    /// A `KUGIRI-OFF` or `KUGIRI-ON` pragma line
+    Pragma,
+}
+
+/// Classify each line so that marker examples in Markdown code blocks and
+/// regions switched off with pragmas are not mistaken for real markers.
+///
+/// Code blocks are only detected when `syntax` is for a Markdown file;
+/// pragmas are honoured in every file type. An indented code block is a run
+/// of lines indented four columns past the enclosing list item, following a
+/// blank line. Lines inside an open section are section content, and an
+/// indented block that starts with a marker holds indented sections, so
+/// neither is taken for code.
+pub fn classify_lines(lines: &[&str], syntax: &MarkerSyntax) -> Vec<LineContext> {
+    let markdown = syntax.is_markdown();
+    let off = syntax.pragma("OFF");
+    let on = syntax.pragma("ON");
+
+    let mut contexts = Vec::with_capacity(lines.len());
+    let mut fence: Option<Fence> = None;
+    let mut disabled = false;
+    let mut prev_blank = true;
+    let mut in_indented_code = false;
+    let mut list_indent: Option<usize> = None;
+    // Ids of the sections open at the current line
+    let mut open: Vec<&str> = Vec::new();
+
+    for line in lines {
+        let trimmed = line.trim();
+        let blank = trimmed.is_empty();
+        let indent = indent_width(line);
+        let code_indent = indent >= list_indent.unwrap_or(0) + 4;
+
+        let context = if let Some(open) = fence {
+            if open.is_closed_by(line) {
+                fence = None;
+            }
+            LineContext::Code
+        } else if markdown && !blank && Fence::open(line).is_some() {
+            fence = Fence::open(line);
+            LineContext::Code
+        } else if markdown
+            && !blank
+            && code_indent
+            && open.is_empty()
+            && (in_indented_code || (prev_blank && syntax.parse_marker(line).is_none()))
+        {
+            in_indented_code = true;
+            LineContext::Code
+        } else if trimmed == off {
+            disabled = true;
+            LineContext::Pragma
+        } else if trimmed == on {
+            disabled = false;
+            LineContext::Pragma
+        } else if disabled {
+            LineContext::Disabled
+        } else {
+            LineContext::Active
+        };
+
+        if context == LineContext::Active {
+            match syntax.parse_marker(line) {
+                Some(marker) if marker.kind == MarkerKind::Begin => open.push(marker.id),
+                Some(marker) if marker.kind == MarkerKind::End => {
+                    if let Some(pos) = open.iter().rposition(|id| *id == marker.id) {
+                        open.truncate(pos);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !blank && context != LineContext::Code {
+            in_indented_code = false;
+        }
+        if markdown && !blank && fence.is_none() && !in_indented_code {
+            if let Some(content_indent) = list_item_indent(line) {
+                list_indent = Some(content_indent);
+            } else if prev_blank && list_indent.is_some_and(|list| indent < list) {
+                list_indent = None;
+            }
+        }
+        prev_blank = blank;
+        contexts.push(context);
+    }
+
+    contexts
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Fence {
+    marker: char,
+    len: usize,
+}
+
+impl Fence {
+    fn open(line: &str) -> Option<Fence> {
+        if indent_width(line) > 3 {
+            return None;
+        }
+        let trimmed = line.trim_start();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+        let len = trimmed.chars().take_while(|c| *c == marker).count();
+        if len < 3 {
+            return None;
+        }
+        // Backtick fences cannot have backticks in their info string
+        if marker == '`' && trimmed[len..].contains('`') {
+            return None;
+        }
+        Some(Fence { marker, len })
+    }
+
+    fn is_closed_by(self, line: &str) -> bool {
+        if indent_width(line) > 3 {
+            return false;
+        }
+        let trimmed = line.trim();
+        trimmed.len() >= self.len && trimmed.chars().all(|c| c == self.marker)
+    }
+}
+
+/// Width of the leading whitespace, counting tabs as four columns.
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Content indent of a Markdown list item line (`- item`, `1. item`).
+fn list_item_indent(line: &str) -> Option<usize> {
+    let indent = indent_width(line);
+    let trimmed = line.trim_start();
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    let marker_len = match trimmed.chars().nth(digits)? {
+        '-' | '*' | '+' if digits == 0 => 1,
+        '.' | ')' if (1..=9).contains(&digits) => digits + 1,
+        _ => return None,
+    };
+    let rest = &trimmed[marker_len..];
+    let spaces = rest.chars().take_while(|c| *c == ' ').count();
+    if spaces == 0 && !rest.is_empty() {
+        return None;
+    }
+    Some(indent + marker_len + spaces.clamp(1, 4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::MarkerStyle;
+    use LineContext::*;
+
+    fn classify(text: &str, syntax: &MarkerSyntax) -> Vec<LineContext> {
+        let lines: Vec<&str> = text.lines().collect();
+        classify_lines(&lines, syntax)
+    }
+
+    #[test]
+    fn test_fenced_code_blocks() {
+        let text = "a\n```markdown\n<!-- KUGIRI-BEGIN: x -->\n```\nb\n~~~~\n```\n~~~~\nc";
+        let markdown = MarkerSyntax::for_path("README.md");
+        assert_eq!(
+            classify(text, &markdown),
+            vec![Active, Code, Code, Code, Active, Code, Code, Code, Active]
+        );
+
+        // Fences mean nothing outside Markdown
+        let plain = MarkerSyntax::for_path("notes.txt");
+        assert!(classify(text, &plain).iter().all(|c| *c == Active));
+    }
+
+    #[test]
+    fn test_indented_code_blocks() {
+        let text = "Example:\n\n    $ kugiri list\n    <!-- KUGIRI-BEGIN: x -->\n\n    <!-- KUGIRI-END: x -->\nParagraph\n    continued";
+        let markdown = MarkerSyntax::for_path("README.md");
+        assert_eq!(
+            classify(text, &markdown),
+            vec![Active, Active, Code, Code, Active, Code, Active, Active]
+        );
+    }
+
+    #[test]
+    fn test_list_items_are_not_indented_code() {
+        let text = "- item\n\n    <!-- KUGIRI-BEGIN: x -->\n    <!-- KUGIRI-END: x -->\n\n        code\n\nafter\n\n    code";
+        let markdown = MarkerSyntax::for_path("README.md");
+        assert_eq!(
+            classify(text, &markdown),
+            vec![Active, Active, Active, Active, Active, Code, Active, Active, Active, Code]
+        );
+    }
+
+    #[test]
+    fn test_indented_sections_are_not_indented_code() {
+        // A block starting with a marker, and the content of open sections
+        let text = "Text\n\n    <!-- KUGIRI-BEGIN: a -->\n    x\n\n        <!-- KUGIRI-BEGIN: b -->\n        y\n        <!-- KUGIRI-END: b -->\n    <!-- KUGIRI-END: a -->\n\n    code";
+        let markdown = MarkerSyntax::for_path("README.md");
+        assert_eq!(
+            classify(text, &markdown),
+            vec![
+                Active, Active, Active, Active, Active, Active, Active, Active, Active, Active,
+                Code
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pragmas() {
+        let text = "# KUGIRI-OFF\n# KUGIRI-BEGIN: x\n# KUGIRI-ON\n# KUGIRI-BEGIN: y";
+        let syntax = MarkerSyntax::new(MarkerStyle::Hash);
+        assert_eq!(
+            classify(text, &syntax),
+            vec![Pragma, Disabled, Pragma, Active]
+        );
+    }
+
+    #[test]
+    fn test_pragmas_inside_fences_are_ignored() {
+        let text = "```\n<!-- KUGIRI-OFF -->\n```\n<!-- KUGIRI-BEGIN: x -->";
+        let markdown = MarkerSyntax::for_path("README.md");
+        assert_eq!(classify(text, &markdown), vec![Code, Code, Code, Active]);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::document::{Document, SectionNode};
+use crate::error::{KugiriError, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Picks sections by id pattern, nesting and attributes.
+///
+/// - `v1.*`: ids matching a glob (`*` any text, `?` one character), at any depth
+/// - `outer/inner`: `inner` sections directly inside an `outer` section
+/// - `example[lang=rust]`: attribute predicates (`[key]` only checks presence)
+/// - `*`: every section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment {
+    pattern: String,
+    predicates: Vec<(String, Option<String>)>,
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Self> {
+        let selector = selector.trim();
+        if selector.is_empty() {
+            return Err(KugiriError::invalid("Selector must not be empty"));
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = selector;
+        loop {
+            let (segment, next) = parse_segment(rest, selector)?;
+            segments.push(segment);
+            match next.strip_prefix('/') {
+                Some(next) => rest = next,
+                None => break,
+            }
+        }
+
+        Ok(Selector {
+            source: selector.to_string(),
+            segments,
+        })
+    }
+
+    /// Whether the selector is a plain id with no patterns or predicates.
+    pub fn as_id(&self) -> Option<&str> {
+        match self.segments.as_slice() {
+            [segment] if segment.predicates.is_empty() && !is_glob(&segment.pattern) => {
+                Some(&segment.pattern)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `node` is matched, checking its ancestors for path selectors.
+    pub fn matches(&self, doc: &Document, node: &SectionNode) -> bool {
+        let (last, parents) = self
+            .segments
+            .split_last()
+            .expect("selectors have a segment");
+        if !last.matches(node) {
+            return false;
+        }
+
+        let mut current = node;
+        for segment in parents.iter().rev() {
+            match doc.parent(current) {
+                Some(parent) if segment.matches(parent) => current = parent,
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// The matching sections in document order. Errors when nothing matches
+    /// or the markers of a matched section are broken.
+    pub fn select<'d>(&self, doc: &'d Document) -> Result<Vec<&'d SectionNode>> {
+        let nodes: Vec<&SectionNode> = doc
+            .sections()
+            .filter(|node| self.matches(doc, node))
+            .collect();
+        for node in &nodes {
+            doc.check_markers(node.id())?;
+        }
+        if nodes.is_empty() {
+            match self.as_id() {
+                Some(id) => doc.require(id).map(|_| ())?,
+                None => {
+                    return Err(KugiriError::NoMatch(format!(
+                        "No section matches selector '{self}'"
+                    )))
+                }
+            }
+        }
+        Ok(nodes)
+    }
+}
+
+impl Segment {
+    fn matches(&self, node: &SectionNode) -> bool {
+        glob_match(&self.pattern, node.id())
+            && self.predicates.iter().all(|(key, value)| {
+                match (node.section.attributes.get(key), value) {
+                    (Some(actual), Some(expected)) => actual == expected,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                }
+            })
+    }
+}
+
+/// Parse one path segment, returning it and the unparsed rest (starting at
+/// `/` or empty).
+fn parse_segment<'a>(text: &'a str, selector: &str) -> Result<(Segment, &'a str)> {
+    let name_end = text.find(['[', '/']).unwrap_or(text.len());
+    let pattern = text[..name_end].trim();
+    let mut rest = &text[name_end..];
+
+    let mut predicates = Vec::new();
+    while let Some(after) = rest.strip_prefix('[') {
+        let Some(close) = after.find(']') else {
+            return Err(KugiriError::invalid(format!(
+                "Invalid selector '{selector}': unclosed '['"
+            )));
+        };
+        let predicate = after[..close].trim();
+        let (key, value) = match predicate.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(unquote(value.trim()).to_string())),
+            None => (predicate, None),
+        };
+        if key.is_empty() {
+            return Err(KugiriError::invalid(format!(
+                "Invalid selector '{selector}': empty attribute name in '[{predicate}]'"
+            )));
+        }
+        predicates.push((key.to_string(), value));
+        rest = after[close + 1..].trim_start();
+    }
+
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return Err(KugiriError::invalid(format!(
+            "Invalid selector '{selector}': unexpected '{rest}'"
+        )));
+    }
+    if pattern.is_empty() && predicates.is_empty() {
+        return Err(KugiriError::invalid(format!(
+            "Invalid selector '{selector}': empty path segment"
+        )));
+    }
+
+    let pattern = if pattern.is_empty() { "*" } else { pattern };
+    Ok((
+        Segment {
+            pattern: pattern.to_string(),
+            predicates,
+        },
+        rest,
+    ))
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Match `text` against a glob where `*` is any run of characters and `?`
+/// a single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for Selector {
+    type Err = KugiriError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Selector::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markers::MarkerSyntax;
+
+    const TEXT: &str = r#"<!-- KUGIRI-BEGIN: guide -->
+<!-- KUGIRI-BEGIN: example lang=rust -->
+fn main() {}
+<!-- KUGIRI-END: example -->
+<!-- KUGIRI-END: guide -->
+<!-- KUGIRI-BEGIN: reference -->
+<!-- KUGIRI-BEGIN: example lang=go -->
+func main() {}
+<!-- KUGIRI-END: example -->
+<!-- KUGIRI-END: reference -->
+<!-- KUGIRI-BEGIN: v0.1 -->
+<!-- KUGIRI-END: v0.1 -->
+<!-- KUGIRI-BEGIN: v0.2 -->
+<!-- KUGIRI-END: v0.2 -->
+<!-- KUGIRI-BEGIN: v1.0 -->
+<!-- KUGIRI-END: v1.0 -->"#;
+
+    fn select(selector: &str) -> Vec<String> {
+        let doc = Document::parse(TEXT, &MarkerSyntax::default());
+        let selector = Selector::parse(selector).unwrap();
+        selector
+            .select(&doc)
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .map(|node| format!("{}@{}", node.id(), node.section.start_line))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_select_by_id_and_glob() {
+        assert_eq!(select("guide"), vec!["guide@0"]);
+        assert_eq!(select("v0.*"), vec!["v0.1@10", "v0.2@12"]);
+        assert_eq!(select("v?.?"), vec!["v0.1@10", "v0.2@12", "v1.0@14"]);
+        assert_eq!(select("*").len(), 7);
+    }
+
+    #[test]
+    fn test_select_paths() {
+        assert_eq!(select("reference/example"), vec!["example@6"]);
+        assert_eq!(select("*/example"), vec!["example@1", "example@6"]);
+        assert_eq!(select("example"), vec!["example@1", "example@6"]);
+        assert!(select("v1.0/example").is_empty());
+    }
+
+    #[test]
+    fn test_select_attributes() {
+        assert_eq!(select("example[lang=go]"), vec!["example@6"]);
+        assert_eq!(select("[lang]"), vec!["example@1", "example@6"]);
+        assert_eq!(select("guide/[lang=\"rust\"]"), vec!["example@1"]);
+        assert!(select("example[lang=c]").is_empty());
+    }
+
+    #[test]
+    fn test_select_errors() {
+        let doc = Document::parse(TEXT, &MarkerSyntax::default());
+
+        let err = Selector::parse("v9.*").unwrap().select(&doc).unwrap_err();
+        assert_eq!(err.to_string(), "No section matches selector 'v9.*'");
+
+        let err = Selector::parse("missing")
+            .unwrap()
+            .select(&doc)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Section with id 'missing' not found");
+
+        assert!(Selector::parse("").is_err());
+        assert!(Selector::parse("a//b").is_err());
+        assert!(Selector::parse("a[lang=rust").is_err());
+        assert!(Selector::parse("a[=rust]").is_err());
+        assert!(Selector::parse("a[x]b").is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("v1.*", "v1.2.3"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(glob_match("?x", "ax"));
+        assert!(!glob_match("v1.*", "v2.0"));
+    }
+}
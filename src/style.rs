@@ -0,0 +1,212 @@
+use crate::error::KugiriError;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Comment dialect used to write KUGIRI markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarkerStyle {
+    /// `<!-- KUGIRI-BEGIN: id -->` (Markdown, HTML, XML)
+    #[default]
+    Html,
+    /// `// KUGIRI-BEGIN: id` (Rust, JavaScript, Go, C, ...)
+    Slash,
+    /// `# KUGIRI-BEGIN: id` (Python, shell, YAML, TOML, ...)
+    Hash,
+    /// `-- KUGIRI-BEGIN: id` (SQL, Lua, Haskell)
+    Dash,
+    /// `; KUGIRI-BEGIN: id` (INI, Lisp, assembly)
+    Semicolon,
+    /// `% KUGIRI-BEGIN: id` (TeX, Erlang, MATLAB)
+    Percent,
+    /// `/* KUGIRI-BEGIN: id */` (CSS)
+    Block,
+    /// `{/* KUGIRI-BEGIN: id */}` (MDX)
+    Mdx,
+    /// `.. KUGIRI-BEGIN: id` (reStructuredText)
+    Rst,
+}
+
+impl MarkerStyle {
+    pub const ALL: [MarkerStyle; 9] = [
+        MarkerStyle::Html,
+        MarkerStyle::Slash,
+        MarkerStyle::Hash,
+        MarkerStyle::Dash,
+        MarkerStyle::Semicolon,
+        MarkerStyle::Percent,
+        MarkerStyle::Block,
+        MarkerStyle::Mdx,
+        MarkerStyle::Rst,
+    ];
+
+    /// Name accepted by `--style`.
+    pub fn name(self) -> &'static str {
+        match self {
+            MarkerStyle::Html => "html",
+            MarkerStyle::Slash => "slash",
+            MarkerStyle::Hash => "hash",
+            MarkerStyle::Dash => "dash",
+            MarkerStyle::Semicolon => "semicolon",
+            MarkerStyle::Percent => "percent",
+            MarkerStyle::Block => "block",
+            MarkerStyle::Mdx => "mdx",
+            MarkerStyle::Rst => "rst",
+        }
+    }
+
+    /// Opening and closing comment delimiters. The closing delimiter is empty
+    /// for line comments.
+    pub fn delimiters(self) -> (&'static str, &'static str) {
+        match self {
+            MarkerStyle::Html => ("<!--", "-->"),
+            MarkerStyle::Slash => ("//", ""),
+            MarkerStyle::Hash => ("#", ""),
+            MarkerStyle::Dash => ("--", ""),
+            MarkerStyle::Semicolon => (";", ""),
+            MarkerStyle::Percent => ("%", ""),
+            MarkerStyle::Block => ("/*", "*/"),
+            MarkerStyle::Mdx => ("{/*", "*/}"),
+            MarkerStyle::Rst => ("..", ""),
+        }
+    }
+
+    /// Wrap `body` in this style's comment delimiters.
+    pub fn comment(self, body: &str) -> String {
+        match self.delimiters() {
+            (open, "") => format!("{open} {body}"),
+            (open, close) => format!("{open} {body} {close}"),
+        }
+    }
+
+    /// Pick a style from a file extension (without the leading dot).
+    pub fn from_extension(ext: &str) -> Option<MarkerStyle> {
+        let style = match ext.to_ascii_lowercase().as_str() {
+            "md" | "markdown" | "html" | "htm" | "xml" | "svg" | "vue" => MarkerStyle::Html,
+            "mdx" => MarkerStyle::Mdx,
+            "rs" | "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" | "go" | "c" | "h" | "cc"
+            | "cpp" | "hpp" | "java" | "kt" | "kts" | "swift" | "scala" | "cs" | "dart" | "zig"
+            | "proto" | "jsonc" | "scss" | "less" => MarkerStyle::Slash,
+            "py" | "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "r" | "ps1" | "yaml" | "yml"
+            | "toml" | "tf" | "nix" | "ex" | "exs" | "cmake" | "mk" | "dockerfile" => {
+                MarkerStyle::Hash
+            }
+            "sql" | "lua" | "hs" | "elm" | "ada" => MarkerStyle::Dash,
+            "ini" | "asm" | "s" | "el" | "lisp" | "clj" | "scm" => MarkerStyle::Semicolon,
+            "tex" | "sty" | "cls" | "erl" | "m" => MarkerStyle::Percent,
+            "css" => MarkerStyle::Block,
+            "rst" => MarkerStyle::Rst,
+            _ => return None,
+        };
+        Some(style)
+    }
+
+    /// Detect the style for a file path, falling back to HTML comments for
+    /// unknown extensions and stdin.
+    pub fn from_path(path: &str) -> MarkerStyle {
+        let path = Path::new(path);
+        if let Some(style) = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(MarkerStyle::from_extension)
+        {
+            return style;
+        }
+
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some("Makefile" | "Dockerfile" | "Containerfile" | "Gemfile" | "Rakefile") => {
+                MarkerStyle::Hash
+            }
+            _ => MarkerStyle::Html,
+        }
+    }
+}
+
+/// Whether a path names a Markdown file, where fenced and indented code
+/// blocks are not scanned for markers.
+pub fn is_markdown_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "md" | "markdown" | "mdx"))
+}
+
+impl fmt::Display for MarkerStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for MarkerStyle {
+    type Err = KugiriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let style = match s {
+            "html" | "<!--" => MarkerStyle::Html,
+            "slash" | "//" => MarkerStyle::Slash,
+            "hash" | "#" => MarkerStyle::Hash,
+            "dash" | "--" => MarkerStyle::Dash,
+            "semicolon" | ";" => MarkerStyle::Semicolon,
+            "percent" | "%" => MarkerStyle::Percent,
+            "block" | "/*" => MarkerStyle::Block,
+            "mdx" => MarkerStyle::Mdx,
+            "rst" | ".." => MarkerStyle::Rst,
+            _ => {
+                let names: Vec<&str> = MarkerStyle::ALL.iter().map(|s| s.name()).collect();
+                return Err(KugiriError::invalid(format!(
+                    "Unknown marker style '{s}' (expected one of: {})",
+                    names.join(", ")
+                )));
+            }
+        };
+        Ok(style)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment() {
+        assert_eq!(MarkerStyle::Html.comment("x"), "<!-- x -->");
+        assert_eq!(MarkerStyle::Slash.comment("x"), "// x");
+        assert_eq!(MarkerStyle::Block.comment("x"), "/* x */");
+        assert_eq!(MarkerStyle::Mdx.comment("x"), "{/* x */}");
+        assert_eq!(MarkerStyle::Rst.comment("x"), ".. x");
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(MarkerStyle::from_path("README.md"), MarkerStyle::Html);
+        assert_eq!(MarkerStyle::from_path("src/lib.rs"), MarkerStyle::Slash);
+        assert_eq!(MarkerStyle::from_path("setup.py"), MarkerStyle::Hash);
+        assert_eq!(MarkerStyle::from_path("config.YML"), MarkerStyle::Hash);
+        assert_eq!(MarkerStyle::from_path("schema.sql"), MarkerStyle::Dash);
+        assert_eq!(MarkerStyle::from_path("init.lua"), MarkerStyle::Dash);
+        assert_eq!(MarkerStyle::from_path("style.css"), MarkerStyle::Block);
+        assert_eq!(MarkerStyle::from_path("docs/page.mdx"), MarkerStyle::Mdx);
+        assert_eq!(MarkerStyle::from_path("index.rst"), MarkerStyle::Rst);
+        assert_eq!(MarkerStyle::from_path("Makefile"), MarkerStyle::Hash);
+        assert_eq!(MarkerStyle::from_path("notes.unknown"), MarkerStyle::Html);
+        assert_eq!(MarkerStyle::from_path("-"), MarkerStyle::Html);
+    }
+
+    #[test]
+    fn test_is_markdown_path() {
+        assert!(is_markdown_path("README.md"));
+        assert!(is_markdown_path("docs/page.MDX"));
+        assert!(!is_markdown_path("index.html"));
+        assert!(!is_markdown_path("-"));
+    }
+
+    #[test]
+    fn test_from_str() {
+        for style in MarkerStyle::ALL {
+            assert_eq!(style.name().parse::<MarkerStyle>().unwrap(), style);
+        }
+        assert_eq!("//".parse::<MarkerStyle>().unwrap(), MarkerStyle::Slash);
+        let err = "nope".parse::<MarkerStyle>().unwrap_err();
+        assert!(err.to_string().contains("Unknown marker style"));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::command::{run_command_with, strip_ansi, CommandOptions};
+use crate::document::{outermost, Document, NodeKind, SectionNode};
+use crate::error::{KugiriError, Result};
+use crate::update::update_each;
+
+/// Regenerate every section with a `cmd` attribute from the output of its
+/// command:
+///
+/// ```markdown
+/// <!-- KUGIRI-BEGIN: help cmd="kugiri --help" fence=text -->
+/// ```
+///
+/// ANSI escape sequences are stripped, and `fence=LANG` wraps the output in
+/// a fenced code block. Commands run one after another in document order;
+/// the first failure is returned and nothing is updated.
+pub fn sync_document(doc: &Document, options: &CommandOptions) -> Result<String> {
+    let nodes: Vec<&SectionNode> = doc
+        .sections()
+        .filter(|node| node.section.attributes.contains_key("cmd"))
+        .collect();
+
+    let mut outputs = Vec::new();
+    for node in outermost(&nodes) {
+        outputs.push((node, section_output(node, options)?));
+    }
+    let targets: Vec<(&SectionNode, &str)> = outputs
+        .iter()
+        .map(|(node, output)| (*node, output.as_str()))
+        .collect();
+    update_each(doc, &targets)
+}
+
+fn section_output(node: &SectionNode, options: &CommandOptions) -> Result<String> {
+    let attributes = &node.section.attributes;
+    let command = &attributes["cmd"];
+    let output = run_command_with(command, options)
+        .map_err(|err| KugiriError::Command(format!("Section '{}': {err}", node.id())))?;
+    fenced(node, strip_ansi(&output))
+}
+
+/// Apply the `fence` attribute of `node` to its new content.
+pub(crate) fn fenced(node: &SectionNode, content: String) -> Result<String> {
+    match node.section.attributes.get("fence") {
+        Some(_) if node.kind == NodeKind::Inline => Err(KugiriError::invalid(format!(
+            "Inline section '{}' can't be fenced",
+            node.id()
+        ))),
+        Some(lang) => Ok(fence(&content, lang)),
+        None => Ok(content),
+    }
+}
+
+/// Wrap `text` in a code fence longer than any backtick run inside it. A
+/// bare `fence` attribute (`true`) gives a fence without a language.
+fn fence(text: &str, lang: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let ticks = "`".repeat(longest.max(2) + 1);
+    let lang = if lang == "true" { "" } else { lang };
+    format!("{ticks}{lang}\n{}\n{ticks}\n", text.trim_end_matches('\n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markers::MarkerSyntax;
+
+    fn sync(text: &str) -> Result<String> {
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        sync_document(&doc, &CommandOptions::default())
+    }
+
+    #[test]
+    fn test_fence() {
+        assert_eq!(fence("a\n", "text"), "```text\na\n```\n");
+        assert_eq!(fence("```\nx\n```", "true"), "````\n```\nx\n```\n````\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_document() {
+        let text = r#"# Usage
+<!-- KUGIRI-BEGIN: help cmd="printf '\\033[1mUsage\\033[0m: tool'" fence=text -->
+stale
+<!-- KUGIRI-END: help -->
+Version <!-- KUGIRI-BEGIN: ver cmd="echo 1.2" -->1.0<!-- KUGIRI-END: ver -->
+<!-- KUGIRI-BEGIN: manual -->
+kept
+<!-- KUGIRI-END: manual -->
+"#;
+        assert_eq!(
+            sync(text).unwrap(),
+            r#"# Usage
+<!-- KUGIRI-BEGIN: help cmd="printf '\\033[1mUsage\\033[0m: tool'" fence=text -->
+```text
+Usage: tool
+```
+<!-- KUGIRI-END: help -->
+Version <!-- KUGIRI-BEGIN: ver cmd="echo 1.2" -->1.2<!-- KUGIRI-END: ver -->
+<!-- KUGIRI-BEGIN: manual -->
+kept
+<!-- KUGIRI-END: manual -->
+"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_reports_failing_section() {
+        let text = "<!-- KUGIRI-BEGIN: a cmd=false -->\n<!-- KUGIRI-END: a -->\n";
+        let err = sync(text).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Section 'a': Command 'false' failed"));
+    }
+}
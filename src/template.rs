@@ -0,0 +1,227 @@
+use crate::error::{KugiriError, Result};
+use std::ops::Range;
+use std::str::FromStr;
+
+pub const ID_PLACEHOLDER: &str = "{id}";
+
+/// A marker format string such as `<!-- KUGIRI-BEGIN: {id} -->`, split around
+/// its `{id}` placeholder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkerTemplate {
+    before: String,
+    after: String,
+}
+
+impl MarkerTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let template = template.trim_start();
+        let Some((before, after)) = template.split_once(ID_PLACEHOLDER) else {
+            return Err(KugiriError::invalid(format!(
+                "Marker template '{template}' must contain {ID_PLACEHOLDER}"
+            )));
+        };
+        if after.contains(ID_PLACEHOLDER) {
+            return Err(KugiriError::invalid(format!(
+                "Marker template '{template}' must contain {ID_PLACEHOLDER} only once"
+            )));
+        }
+        if before.trim().is_empty() && after.trim().is_empty() {
+            return Err(KugiriError::invalid(format!(
+                "Marker template '{template}' needs text around {ID_PLACEHOLDER}"
+            )));
+        }
+        Ok(MarkerTemplate {
+            before: before.to_string(),
+            after: after.to_string(),
+        })
+    }
+
+    pub fn render(&self, id: &str) -> String {
+        format!("{}{id}{}", self.before, self.after)
+    }
+
+    /// Return the text in place of `{id}` (the id followed by any attributes)
+    /// if `line`, already stripped of its indent, is this marker.
+    pub fn capture<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let captured = line
+            .strip_prefix(self.before.as_str())?
+            .strip_suffix(self.after.as_str())?;
+        self.valid_capture(captured)
+    }
+
+    /// Like [`MarkerTemplate::capture`], but spacing may differ from the
+    /// template wherever the template has whitespace, so that
+    /// `<!--KUGIRI-BEGIN:x-->` is read as `<!-- KUGIRI-BEGIN: {id} -->`.
+    pub fn capture_loose<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let rest = strip_loose(line.trim_end(), self.before.chars())?;
+        let len = strip_loose_end(rest, self.after.chars().rev())?;
+        self.valid_capture(rest[..len].trim_start())
+    }
+
+    /// Return the id if `line` (already stripped of its indent) is this marker.
+    pub fn match_id<'a>(&self, line: &'a str) -> Option<&'a str> {
+        self.capture(line).map(|captured| split_id(captured).0)
+    }
+
+    /// Find this marker embedded in `line` at or after byte offset `from`,
+    /// returning its byte range and captured text. Only templates with text on both sides
+    /// of `{id}` can be found inside a line.
+    pub fn find_in<'a>(&self, line: &'a str, from: usize) -> Option<(Range<usize>, &'a str)> {
+        if self.before.trim().is_empty() || self.after.trim().is_empty() {
+            return None;
+        }
+
+        let mut pos = from;
+        while let Some(offset) = line.get(pos..)?.find(self.before.as_str()) {
+            let start = pos + offset;
+            let id_start = start + self.before.len();
+            if let Some(len) = line[id_start..].find(self.after.as_str()) {
+                if let Some(captured) = self.valid_capture(&line[id_start..id_start + len]) {
+                    return Some((start..id_start + len + self.after.len(), captured));
+                }
+            }
+            pos = id_start;
+        }
+        None
+    }
+
+    fn valid_capture<'a>(&self, captured: &'a str) -> Option<&'a str> {
+        let captured = captured.trim_end();
+        if captured.is_empty() || captured.starts_with(char::is_whitespace) {
+            return None;
+        }
+        // Text such as `<!-- KUGIRI-END: a --> <!-- note -->` is not one marker
+        if self.delimiters().any(|d| captured.contains(d)) {
+            return None;
+        }
+        Some(captured)
+    }
+
+    /// The literal text around the id, ignoring surrounding whitespace.
+    pub fn delimiters(&self) -> impl Iterator<Item = &str> {
+        [self.before.trim(), self.after.trim()]
+            .into_iter()
+            .filter(|d| !d.is_empty())
+    }
+}
+
+/// Strip `pattern` from the start of `text`, letting each run of whitespace
+/// in the pattern match any amount of whitespace, including none.
+fn strip_loose(text: &str, pattern: impl Iterator<Item = char>) -> Option<&str> {
+    let mut rest = text;
+    for c in pattern {
+        if c.is_whitespace() {
+            rest = rest.trim_start();
+        } else {
+            rest = rest.strip_prefix(c)?;
+        }
+    }
+    Some(rest)
+}
+
+/// Like [`strip_loose`] from the end of `text`, with `pattern` reversed,
+/// returning the length of what is left.
+fn strip_loose_end(text: &str, pattern: impl Iterator<Item = char>) -> Option<usize> {
+    let mut rest = text;
+    for c in pattern {
+        if c.is_whitespace() {
+            rest = rest.trim_end();
+        } else {
+            rest = rest.strip_suffix(c)?;
+        }
+    }
+    Some(rest.len())
+}
+
+/// Split captured marker text into the id and the attribute text after it.
+pub fn split_id(captured: &str) -> (&str, &str) {
+    match captured.split_once(char::is_whitespace) {
+        Some((id, attributes)) => (id, attributes.trim_start()),
+        None => (captured, ""),
+    }
+}
+
+impl FromStr for MarkerTemplate {
+    type Err = KugiriError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        MarkerTemplate::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_match() {
+        let template = MarkerTemplate::parse("# >>> {id} >>>").unwrap();
+        assert_eq!(template.render("deps"), "# >>> deps >>>");
+        assert_eq!(template.match_id("# >>> deps >>>"), Some("deps"));
+        assert_eq!(template.match_id("# >>> deps"), None);
+        assert_eq!(template.match_id("# >>>  >>>"), None);
+        assert_eq!(template.match_id("# >>> a b >>>"), Some("a"));
+        assert_eq!(template.capture("# >>> a  b=1  >>>"), Some("a  b=1"));
+        assert_eq!(template.capture("# >>> a >>> b >>>"), None);
+    }
+
+    #[test]
+    fn test_capture_loose() {
+        let template = MarkerTemplate::parse("<!-- KUGIRI-BEGIN: {id} -->").unwrap();
+        assert_eq!(template.capture_loose("<!--KUGIRI-BEGIN:x-->"), Some("x"));
+        assert_eq!(
+            template.capture_loose("<!--   KUGIRI-BEGIN:  x  lang=rust-->"),
+            Some("x  lang=rust")
+        );
+        assert_eq!(template.capture_loose("<!--KUGIRI-BEGIN:-->"), None);
+        assert_eq!(template.capture_loose("<!-- KUGIRI-END: x -->"), None);
+
+        let line_comment = MarkerTemplate::parse("// KUGIRI-END: {id}").unwrap();
+        assert_eq!(line_comment.capture_loose("//KUGIRI-END:a  "), Some("a"));
+    }
+
+    #[test]
+    fn test_trailing_id() {
+        let template = MarkerTemplate::parse("// region {id}").unwrap();
+        assert_eq!(template.match_id("// region setup"), Some("setup"));
+        assert_eq!(template.match_id("// endregion setup"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = MarkerTemplate::parse("# begin").unwrap_err();
+        assert!(err.to_string().contains("must contain {id}"));
+
+        let err = MarkerTemplate::parse("{id} {id}").unwrap_err();
+        assert!(err.to_string().contains("only once"));
+
+        let err = MarkerTemplate::parse(" {id} ").unwrap_err();
+        assert!(err.to_string().contains("needs text"));
+    }
+
+    #[test]
+    fn test_find_in() {
+        let template = MarkerTemplate::parse("<!-- KUGIRI-END: {id} -->").unwrap();
+        let line = "v<!-- KUGIRI-END:  --> and <!-- KUGIRI-END: ver --> done";
+        let (range, id) = template.find_in(line, 0).unwrap();
+        assert_eq!(id, "ver");
+        assert_eq!(&line[range.clone()], "<!-- KUGIRI-END: ver -->");
+        assert_eq!(template.find_in(line, range.end), None);
+
+        let line_comment = MarkerTemplate::parse("// KUGIRI-END: {id}").unwrap();
+        assert_eq!(line_comment.find_in("x // KUGIRI-END: a", 0), None);
+    }
+
+    #[test]
+    fn test_split_id() {
+        assert_eq!(split_id("api lang=rust  x=1"), ("api", "lang=rust  x=1"));
+        assert_eq!(split_id("api"), ("api", ""));
+    }
+
+    #[test]
+    fn test_delimiters() {
+        let template = MarkerTemplate::parse("<!-- KUGIRI-BEGIN: {id} -->").unwrap();
+        let delimiters: Vec<&str> = template.delimiters().collect();
+        assert_eq!(delimiters, vec!["<!-- KUGIRI-BEGIN:", "-->"]);
+    }
+}
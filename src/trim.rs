@@ -1,26 +1,120 @@
+use crate::document::{Document, NodeKind};
+use crate::error::Result;
+use crate::markers::{InlineSpan, MarkerSyntax};
+use crate::scanner::LineContext;
+use crate::selector::Selector;
 use crate::utils::join_lines_preserve_trailing_newline;
+use std::collections::{BTreeMap, HashSet};
 
 pub fn trim(text: &str) -> String {
-    let lines: Vec<&str> = text.lines().collect();
+    trim_with(text, &MarkerSyntax::default())
+}
+
+pub fn trim_with(text: &str, syntax: &MarkerSyntax) -> String {
+    let doc = Document::parse(text, syntax);
     let mut result: Vec<String> = Vec::new();
+    let mut inline = doc
+        .sections()
+        .filter(|node| node.kind == NodeKind::Inline)
+        .peekable();
 
-    for &line in lines.iter() {
-        if line.starts_with("<!-- KUGIRI-BEGIN:")
-            || line.starts_with("<!-- KUGIRI-END:")
-            || line.starts_with("<!-- KUGIRI-INSERT:")
+    for (idx, &line) in doc.lines().iter().enumerate() {
+        // Only markers and pragmas that start at column 0 are removed
+        let at_column_0 = !line.starts_with(char::is_whitespace);
+        match doc.line_context(idx) {
+            LineContext::Active if at_column_0 && doc.is_marker_line(idx) => continue,
+            LineContext::Pragma if at_column_0 => continue,
+            LineContext::Active => {}
+            _ => {
+                result.push(line.to_string());
+                continue;
+            }
+        }
+
+        // Keep the content of inline sections, dropping their markers
+        let mut trimmed = String::new();
+        let mut pos = 0;
+        while let Some(span) = inline
+            .next_if(|node| node.section.start_line == idx)
+            .and_then(|node| node.section.inline.as_ref())
         {
-            continue;
+            trimmed.push_str(&line[pos..span.outer.start]);
+            trimmed.push_str(&line[span.inner.clone()]);
+            pos = span.outer.end;
         }
-        result.push(line.to_string());
+        trimmed.push_str(&line[pos..]);
+        result.push(trimmed);
     }
 
     join_lines_preserve_trailing_newline(result, text)
 }
 
+/// Remove only the markers of the sections matched by `selector`, keeping
+/// their content. Unlike [`trim_with`], indented markers are removed too.
+pub fn trim_selected(text: &str, selector: &Selector, syntax: &MarkerSyntax) -> Result<String> {
+    let doc = Document::parse(text, syntax);
+    let mut marker_lines = HashSet::new();
+    let mut inline: BTreeMap<usize, Vec<&InlineSpan>> = BTreeMap::new();
+    for node in selector.select(&doc)? {
+        match &node.section.inline {
+            Some(span) => inline
+                .entry(node.section.start_line)
+                .or_default()
+                .push(span),
+            None => {
+                marker_lines.insert(node.section.start_line);
+                marker_lines.insert(node.section.end_line);
+            }
+        }
+    }
+
+    let mut result: Vec<String> = Vec::new();
+    for (idx, &line) in doc.lines().iter().enumerate() {
+        if marker_lines.contains(&idx) {
+            continue;
+        }
+        match inline.get(&idx) {
+            Some(spans) => {
+                let mut trimmed = String::new();
+                let mut pos = 0;
+                for span in spans {
+                    trimmed.push_str(&line[pos..span.outer.start]);
+                    trimmed.push_str(&line[span.inner.clone()]);
+                    pos = span.outer.end;
+                }
+                trimmed.push_str(&line[pos..]);
+                result.push(trimmed);
+            }
+            None => result.push(line.to_string()),
+        }
+    }
+
+    Ok(join_lines_preserve_trailing_newline(result, text))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_trim_selected() {
+        let text = r#"<!-- KUGIRI-BEGIN: v0.1 -->
+  <!-- KUGIRI-BEGIN: v0.2 -->
+  old
+  <!-- KUGIRI-END: v0.2 -->
+<!-- KUGIRI-END: v0.1 -->
+<!-- KUGIRI-BEGIN: v1.0 -->
+Version <!-- KUGIRI-BEGIN: v0.3 -->0.3<!-- KUGIRI-END: v0.3 -->
+<!-- KUGIRI-END: v1.0 -->"#;
+
+        let selector = Selector::parse("v0.*").unwrap();
+        let result = trim_selected(text, &selector, &MarkerSyntax::default()).unwrap();
+        assert_eq!(
+            result,
+            "  old\n<!-- KUGIRI-BEGIN: v1.0 -->\nVersion 0.3\n<!-- KUGIRI-END: v1.0 -->"
+        );
+    }
+
     #[test]
     fn test_trim_removes_all_markers() {
         let text = r#"Header
@@ -52,6 +146,58 @@ Footer"#;
         assert!(!result.contains("KUGIRI-INSERT"));
     }
 
+    #[test]
+    fn test_trim_with_hash_style() {
+        let text = "a: 1\n# KUGIRI-BEGIN: gen\nb: 2\n# KUGIRI-END: gen\n# a regular comment\n";
+        let syntax = MarkerSyntax::new(crate::style::MarkerStyle::Hash);
+
+        assert_eq!(
+            trim_with(text, &syntax),
+            "a: 1\nb: 2\n# a regular comment\n"
+        );
+    }
+
+    #[test]
+    fn test_trim_with_templates() {
+        let text = "a\n;; begin(x)\nb\n;; end(x)\n;; begin (not a marker)\n";
+        let syntax = MarkerSyntax::new(crate::style::MarkerStyle::Semicolon)
+            .with_templates(
+                ";; begin({id})".parse().unwrap(),
+                ";; end({id})".parse().unwrap(),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(trim_with(text, &syntax), "a\nb\n;; begin (not a marker)\n");
+    }
+
+    #[test]
+    fn test_trim_inline_sections() {
+        let text = "Version <!-- KUGIRI-BEGIN: ver -->1.2.3<!-- KUGIRI-END: ver --> on <!-- KUGIRI-BEGIN: os -->linux<!-- KUGIRI-END: os -->";
+        assert_eq!(trim(text), "Version 1.2.3 on linux");
+    }
+
+    #[test]
+    fn test_trim_keeps_documented_markers() {
+        let text = r#"<!-- KUGIRI-BEGIN: a -->
+Real
+<!-- KUGIRI-END: a -->
+
+```
+<!-- KUGIRI-BEGIN: example -->
+```
+
+<!-- KUGIRI-OFF -->
+<!-- KUGIRI-INSERT: literal -->
+<!-- KUGIRI-ON -->"#;
+
+        let syntax = MarkerSyntax::for_path("README.md");
+        assert_eq!(
+            trim_with(text, &syntax),
+            "Real\n\n```\n<!-- KUGIRI-BEGIN: example -->\n```\n\n<!-- KUGIRI-INSERT: literal -->"
+        );
+    }
+
     #[test]
     fn test_trim_empty_file() {
         assert_eq!(trim(""), "");
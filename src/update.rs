@@ -1,52 +1,224 @@
-use crate::markers::find_section;
+use crate::attributes::set_attribute_text;
+use crate::document::{outermost, Document, NodeKind, Occurrence, SectionNode};
+use crate::error::{KugiriError, Result};
+use crate::markers::{MarkerKind, MarkerSyntax};
+use crate::selector::Selector;
 use crate::utils::join_lines_preserve_trailing_newline;
-use anyhow::Result;
 
 pub fn update(text: &str, id: &str, content: &str) -> Result<String> {
-    let section = find_section(text, id)
-        .ok_or_else(|| anyhow::anyhow!("Section with id '{id}' not found"))?;
+    update_with(text, id, content, &MarkerSyntax::default())
+}
+
+pub fn update_with(text: &str, id: &str, content: &str, syntax: &MarkerSyntax) -> Result<String> {
+    update_occurrences(text, id, content, Occurrence::First, syntax)
+}
+
+/// Update the sections with this id picked by `occurrence`.
+pub fn update_occurrences(
+    text: &str,
+    id: &str,
+    content: &str,
+    occurrence: Occurrence,
+    syntax: &MarkerSyntax,
+) -> Result<String> {
+    syntax.validate_id(id)?;
+    let doc = Document::parse(text, syntax);
+    let nodes = doc.require_occurrences(id, occurrence)?;
+    update_document(&doc, &nodes, content)
+}
+
+/// Update the sections matched by `selector`, narrowed down by `occurrence`.
+pub fn update_selected(
+    text: &str,
+    selector: &Selector,
+    content: &str,
+    occurrence: Occurrence,
+    syntax: &MarkerSyntax,
+) -> Result<String> {
+    let doc = Document::parse(text, syntax);
+    let nodes = occurrence.select(selector.select(&doc)?, &selector.to_string())?;
+    update_document(&doc, &nodes, content)
+}
+
+/// Replace the content of `nodes`; sections nested in another one of them
+/// are replaced along with it.
+pub fn update_document(doc: &Document, nodes: &[&SectionNode], content: &str) -> Result<String> {
+    let targets: Vec<_> = outermost(nodes)
+        .into_iter()
+        .map(|node| (node, content))
+        .collect();
+    update_each(doc, &targets)
+}
 
-    let lines: Vec<&str> = text.lines().collect();
+/// Replace the content of each section with its own text. `targets` must be
+/// in document order and not nested in each other.
+pub(crate) fn update_each(doc: &Document, targets: &[(&SectionNode, &str)]) -> Result<String> {
+    let lines = doc.lines();
     let mut result: Vec<String> = Vec::new();
 
-    // Trim trailing newline from content
-    let content_trimmed = content.trim_end_matches('\n');
+    for (node, content) in targets {
+        if node.kind == NodeKind::Inline && content.trim_end_matches('\n').contains('\n') {
+            return Err(KugiriError::invalid(format!(
+                "Inline section '{}' can only hold a single line of content",
+                node.id()
+            )));
+        }
+    }
+
+    let mut targets = targets.iter().peekable();
+    let mut skip_until = None;
+
+    for (idx, &line) in lines.iter().enumerate() {
+        if let Some(end) = skip_until {
+            if idx < end {
+                // Skip old content
+                continue;
+            }
+            skip_until = None;
+        }
+
+        let mut here = Vec::new();
+        while let Some((node, content)) =
+            targets.next_if(|(node, _)| node.section.start_line == idx)
+        {
+            // Trim trailing newline from content
+            here.push((node, content.trim_end_matches('\n')));
+        }
+
+        match here.first() {
+            Some((node, content)) if node.kind == NodeKind::Block => {
+                // Keep the begin marker
+                result.push(line.to_string());
+                // Add new content with proper indentation
+                result.push(indent_content(content, &node.section.indent));
+                // Skip to the end marker
+                skip_until = Some(node.section.end_line);
+            }
+            Some(_) => {
+                // Replace only the text between the markers
+                let mut updated = String::new();
+                let mut pos = 0;
+                for (span, content) in here
+                    .iter()
+                    .filter_map(|(node, content)| Some((node.section.inline.as_ref()?, content)))
+                {
+                    updated.push_str(&line[pos..span.inner.start]);
+                    updated.push_str(content);
+                    pos = span.inner.end;
+                }
+                updated.push_str(&line[pos..]);
+                result.push(updated);
+            }
+            None => result.push(line.to_string()),
+        }
+    }
+
+    Ok(join_lines_preserve_trailing_newline(result, doc.text()))
+}
+
+/// Set `key=value` attributes in the BEGIN markers of sections, leaving
+/// their other attributes and content untouched. A section may appear
+/// several times to set several keys.
+pub(crate) fn set_attributes(doc: &Document, changes: &[(&SectionNode, &str, String)]) -> String {
+    let mut lines: Vec<String> = doc.lines().iter().map(|line| line.to_string()).collect();
+
+    let mut nodes: Vec<&SectionNode> = Vec::new();
+    for (node, _, _) in changes {
+        if !nodes.iter().any(|seen| std::ptr::eq(*seen, *node)) {
+            nodes.push(node);
+        }
+    }
+    // Later markers first, so that earlier spans on the same line stay valid
+    nodes.sort_by_key(|node| {
+        let start = node
+            .section
+            .inline
+            .as_ref()
+            .map_or(0, |span| span.outer.start);
+        std::cmp::Reverse((node.section.start_line, start))
+    });
+
+    for node in nodes {
+        let line = &mut lines[node.section.start_line];
+        let span = match &node.section.inline {
+            Some(span) => span.outer.start..span.inner.start,
+            None => node.section.indent.len()..line.trim_end().len(),
+        };
+        let Some(marker) = doc.syntax().parse_marker(&line[span.clone()]) else {
+            continue;
+        };
+        let mut attributes = marker.attributes.to_string();
+        for (_, key, value) in changes.iter().filter(|(n, _, _)| std::ptr::eq(*n, node)) {
+            attributes = set_attribute_text(&attributes, key, value);
+        }
+        let rendered =
+            doc.syntax()
+                .marker_with_attributes(MarkerKind::Begin, marker.id, &attributes);
+        line.replace_range(span, &rendered);
+    }
+
+    join_lines_preserve_trailing_newline(lines, doc.text())
+}
 
-    // Add indent to each line of new content
-    let indented_content = content_trimmed
+/// Add indent to each line of new content
+pub(crate) fn indent_content(content: &str, indent: &str) -> String {
+    content
         .lines()
         .map(|line| {
             if line.is_empty() {
                 line.to_string()
             } else {
-                format!("{}{}", section.indent, line)
+                format!("{indent}{line}")
             }
         })
         .collect::<Vec<_>>()
-        .join("\n");
-
-    for (idx, &line) in lines.iter().enumerate() {
-        if idx == section.start_line {
-            // Keep the begin marker
-            result.push(line.to_string());
-            // Add new content with proper indentation
-            result.push(indented_content.clone());
-            // Skip to the end marker
-        } else if idx > section.start_line && idx < section.end_line {
-            // Skip old content
-            continue;
-        } else {
-            result.push(line.to_string());
-        }
-    }
-
-    Ok(join_lines_preserve_trailing_newline(result, text))
+        .join("\n")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_update_occurrences() {
+        let text = r#"<!-- KUGIRI-BEGIN: v1.0 -->
+first
+<!-- KUGIRI-END: v1.0 -->
+<!-- KUGIRI-BEGIN: v1.0 -->
+second
+<!-- KUGIRI-END: v1.0 -->"#;
+        let syntax = MarkerSyntax::default();
+
+        let result = update_occurrences(text, "v1.0", "new", Occurrence::Nth(2), &syntax).unwrap();
+        assert!(result.contains("first"));
+        assert!(!result.contains("second"));
+
+        let result = update_occurrences(text, "v1.0", "new", Occurrence::All, &syntax).unwrap();
+        assert_eq!(result.matches("new").count(), 2);
+        assert!(!result.contains("first"));
+
+        // The default keeps acting on the first section
+        let result = update(text, "v1.0", "new").unwrap();
+        assert!(!result.contains("first"));
+        assert!(result.contains("second"));
+    }
+
+    #[test]
+    fn test_update_reports_broken_markers() {
+        let crossed = r#"<!-- KUGIRI-BEGIN: outer -->
+<!-- KUGIRI-BEGIN: inner -->
+<!-- KUGIRI-END: outer -->
+<!-- KUGIRI-END: inner -->"#;
+        let err = update(crossed, "outer", "x").unwrap_err();
+        assert!(err.to_string().contains("cross"));
+        let err = update(crossed, "inner", "x").unwrap_err();
+        assert!(err.to_string().contains("cross"));
+
+        let unclosed = "<!-- KUGIRI-BEGIN: notes -->\nold";
+        let err = update(unclosed, "notes", "x").unwrap_err();
+        assert!(err.to_string().contains("line 1 but not end marker"));
+    }
+
     #[test]
     fn test_update_existing_section() {
         let text = r#"# README
@@ -100,6 +272,28 @@ Outer content end
         assert!(result.contains("  New inner content"));
     }
 
+    #[test]
+    fn test_update_inline_section() {
+        let text = "Current version: <!-- KUGIRI-BEGIN: ver -->1.2.3<!-- KUGIRI-END: ver --> (stable)\nNext line\n";
+
+        let result = update(text, "ver", "1.3.0\n").unwrap();
+
+        assert_eq!(
+            result,
+            "Current version: <!-- KUGIRI-BEGIN: ver -->1.3.0<!-- KUGIRI-END: ver --> (stable)\nNext line\n"
+        );
+    }
+
+    #[test]
+    fn test_update_inline_section_rejects_multiline() {
+        let text = "v<!-- KUGIRI-BEGIN: ver -->1<!-- KUGIRI-END: ver -->";
+
+        let result = update(text, "ver", "1\n2");
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("single line"));
+    }
+
     #[test]
     fn test_update_preserves_surrounding_content() {
         let text = r#"Header content
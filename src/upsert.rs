@@ -1,7 +1,8 @@
-use crate::insert::insert;
-use crate::markers::find_section;
-use crate::update::update;
-use anyhow::Result;
+use crate::document::{Document, Occurrence};
+use crate::error::{KugiriError, Result};
+use crate::insert::insert_document;
+use crate::markers::MarkerSyntax;
+use crate::update::update_document;
 
 pub fn upsert(
     text: &str,
@@ -10,20 +11,78 @@ pub fn upsert(
     before: Option<&str>,
     after: Option<&str>,
 ) -> Result<String> {
-    // Check if section exists
-    if find_section(text, id).is_some() {
-        // Section exists, update it
-        update(text, id, content)
-    } else {
+    upsert_with(text, id, content, before, after, &MarkerSyntax::default())
+}
+
+pub fn upsert_with(
+    text: &str,
+    id: &str,
+    content: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+    syntax: &MarkerSyntax,
+) -> Result<String> {
+    upsert_document(
+        &Document::parse(text, syntax),
+        id,
+        content,
+        before,
+        after,
+        false,
+    )
+}
+
+/// Update the section with this id, or insert it if there is none. When the
+/// id already occurs several times, only `allow_duplicate` lets the first one
+/// be updated.
+pub fn upsert_document(
+    doc: &Document,
+    id: &str,
+    content: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+    allow_duplicate: bool,
+) -> Result<String> {
+    doc.syntax().validate_id(id)?;
+    // Check if section exists; broken markers for it are reported by update
+    if doc.get(id).is_none() && !doc.problems().iter().any(|problem| problem.involves(id)) {
         // Section doesn't exist, insert it
-        insert(text, id, content, before, after)
+        return insert_document(doc, id, content, before, after, allow_duplicate);
     }
+
+    // Section exists, update it
+    let nodes = doc.require_occurrences(id, Occurrence::All)?;
+    if nodes.len() > 1 && !allow_duplicate {
+        return Err(KugiriError::Duplicate(format!(
+            "Section with id '{id}' occurs {} times; use --allow-duplicate to update the first",
+            nodes.len()
+        )));
+    }
+    update_document(doc, &nodes[..1], content)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_upsert_refuses_ambiguous_duplicates() {
+        let text = r#"<!-- KUGIRI-BEGIN: v2 -->
+a
+<!-- KUGIRI-END: v2 -->
+<!-- KUGIRI-BEGIN: v2 -->
+b
+<!-- KUGIRI-END: v2 -->"#;
+
+        let err = upsert(text, "v2", "new", None, None).unwrap_err();
+        assert!(err.to_string().contains("occurs 2 times"));
+
+        let doc = Document::parse(text, &MarkerSyntax::default());
+        let result = upsert_document(&doc, "v2", "new", None, None, true).unwrap();
+        assert!(!result.contains('a'));
+        assert!(result.contains('b'));
+    }
+
     #[test]
     fn test_upsert_updates_existing() {
         let text = r#"# README
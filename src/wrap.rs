@@ -1,12 +1,22 @@
-use crate::markers::{make_begin_marker, make_end_marker};
+use crate::error::Result;
+use crate::markers::MarkerSyntax;
 
 pub fn wrap(content: &str, id: &str) -> String {
+    wrap_markers(content, id, &MarkerSyntax::default())
+}
+
+pub fn wrap_with(content: &str, id: &str, syntax: &MarkerSyntax) -> Result<String> {
+    syntax.validate_id(id)?;
+    Ok(wrap_markers(content, id, syntax))
+}
+
+fn wrap_markers(content: &str, id: &str, syntax: &MarkerSyntax) -> String {
     let content_trimmed = content.trim_end_matches('\n');
     format!(
         "{}\n{}\n{}",
-        make_begin_marker(id),
+        syntax.begin_marker(id),
         content_trimmed,
-        make_end_marker(id)
+        syntax.end_marker(id)
     )
 }
 
@@ -48,6 +58,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_wrap_with_templates() {
+        let syntax = MarkerSyntax::default()
+            .with_templates(
+                "<!-- begin {id} -->".parse().unwrap(),
+                "<!-- end {id} -->".parse().unwrap(),
+                None,
+            )
+            .unwrap();
+
+        let result = wrap_with("Body", "x", &syntax).unwrap();
+        assert_eq!(result, "<!-- begin x -->\nBody\n<!-- end x -->");
+
+        assert!(wrap_with("Body", "x -->", &syntax).is_err());
+    }
+
     #[test]
     fn test_wrap_empty_content() {
         let content = "";
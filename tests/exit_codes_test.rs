@@ -0,0 +1,53 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn kugiri(args: &[&str], stdin: &str) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_kugiri"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_exit_codes() {
+    let fixture = "tests/fixtures/duplicates.md";
+
+    assert_eq!(kugiri(&["extract", fixture, "--id", "v1.1.0"], "").0, 0);
+
+    let (code, stderr) = kugiri(&["extract", fixture, "--id", "missing"], "");
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("error: Section with id 'missing' not found\n"));
+
+    let (code, _) = kugiri(
+        &["trim", "-", "--select", "notes"],
+        "<!-- KUGIRI-BEGIN: notes -->\n",
+    );
+    assert_eq!(code, 3);
+
+    let (code, stderr) = kugiri(&["extract", "tests/fixtures/missing.md", "--id", "a"], "");
+    assert_eq!(code, 4);
+    assert!(stderr.starts_with("error: tests/fixtures/missing.md: "));
+
+    assert_eq!(kugiri(&["extract", fixture, "--id", "bad id"], "").0, 5);
+    assert_eq!(kugiri(&["extract", fixture], "").0, 5);
+
+    let (code, _) = kugiri(
+        &["insert", fixture, "--id", "v1.1.0", "--after", "v1.1.0"],
+        "x",
+    );
+    assert_eq!(code, 6);
+}
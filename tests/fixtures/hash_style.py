@@ -0,0 +1,7 @@
+"""Generated constants."""
+
+# KUGIRI-BEGIN: constants
+VERSION = "0.0.0"
+# KUGIRI-END: constants
+
+    # KUGIRI-INSERT: extra
@@ -87,6 +87,9 @@ run_test "extract_nested_inner" \
 run_test "extract_indented" \
     "$KUGIRI extract fixtures/indented.md --id indented-section"
 
+run_test "extract_four_space_nested" \
+    "$KUGIRI extract fixtures/test_nested.md --id subsection1-1-1"
+
 # Update tests
 run_test "update_basic" \
     "echo 'Updated content' | $KUGIRI update fixtures/basic.md --id section1"
@@ -135,6 +138,228 @@ run_test "upsert_existing" \
 run_test "upsert_new" \
     "echo 'New upserted content' | $KUGIRI upsert fixtures/basic.md --id new-section --after section1"
 
+# Comment style tests
+run_test "update_hash_style" \
+    "echo 'VERSION = \"1.2.3\"' | $KUGIRI update fixtures/hash_style.py --id constants"
+
+run_test "insert_hash_style" \
+    "echo 'DEBUG = False' | $KUGIRI insert fixtures/hash_style.py --id debug --after extra"
+
+run_test "wrap_style_override" \
+    "echo 'Content to wrap' | $KUGIRI wrap --id wrapped-section --style slash"
+
+# Marker prefix tests
+run_test "migrate_prefix" \
+    "$KUGIRI migrate-markers fixtures/with_insert.md --to-prefix DOCGEN"
+
+run_test "migrate_style" \
+    "$KUGIRI migrate-markers fixtures/hash_style.py --to-style slash"
+
+run_test "update_marker_prefix" \
+    "echo 'Updated by docgen.' | $KUGIRI update fixtures/docgen.md --id usage --marker-prefix DOCGEN"
+
+# Custom template tests
+run_test "update_templates" \
+    "echo 'anyhow = \"1\"' | $KUGIRI update fixtures/templates.toml --id managed-deps --begin-template '# >>> {id} >>>' --end-template '# <<< {id} <<<'"
+
+run_test "migrate_from_templates" \
+    "$KUGIRI migrate-markers fixtures/templates.toml --begin-template '# >>> {id} >>>' --end-template '# <<< {id} <<<' --to-style hash"
+
+# Inline section tests
+run_test "extract_inline" \
+    "$KUGIRI extract fixtures/inline.md --id version"
+
+run_test "update_inline" \
+    "echo '1.3.0' | $KUGIRI update fixtures/inline.md --id version"
+
+run_test "remove_inline" \
+    "$KUGIRI remove fixtures/inline.md --id ci-badge"
+
+run_test "trim_inline" \
+    "$KUGIRI trim fixtures/inline.md"
+
+# Attribute tests
+run_test "extract_by_attribute" \
+    "$KUGIRI extract fixtures/attributes.md --attr lang=go"
+
+run_test "update_keeps_attributes" \
+    "echo 'fn main() { println!(\"hi\"); }' | $KUGIRI update fixtures/attributes.md --id rust-example"
+
+# Code block and pragma tests
+run_test "update_skips_fenced_example" \
+    "echo 'Updated usage.' | $KUGIRI update fixtures/fenced.md --id usage"
+
+run_test "trim_keeps_fenced_example" \
+    "$KUGIRI trim fixtures/fenced.md"
+
+# Convert tests
+run_test "convert_markdown_magic" \
+    "$KUGIRI convert fixtures/markdown_magic.md --from markdown-magic"
+
+run_test "update_after_convert" \
+    "$KUGIRI convert fixtures/markdown_magic.md --from markdown-magic | $KUGIRI update /dev/stdin --id toc --body-file fixtures/content.txt"
+
+# Duplicate id tests
+run_test "extract_occurrence" \
+    "$KUGIRI extract fixtures/duplicates.md --id v1.2.0 --occurrence 2"
+
+run_test "remove_all_occurrences" \
+    "$KUGIRI remove fixtures/duplicates.md --id v1.2.0 --all"
+
+run_test "insert_duplicate_allowed" \
+    "echo '## v1.1.0 again' | $KUGIRI insert fixtures/duplicates.md --id v1.1.0 --after top --allow-duplicate"
+
+# Selector tests
+run_test "update_selector_path" \
+    "echo 'fn main() { run(); }' | $KUGIRI update fixtures/selectors.md --select guide/example"
+
+run_test "remove_selector_glob" \
+    "$KUGIRI remove fixtures/selectors.md --select 'v0.*'"
+
+run_test "extract_selector_attribute" \
+    "$KUGIRI extract fixtures/selectors.md --select '*[lang=go]'"
+
+run_test "trim_selector" \
+    "$KUGIRI trim fixtures/selectors.md --select 'reference'"
+
+# List tests
+run_test "list_basic" \
+    "$KUGIRI list fixtures/nested.md"
+
+run_test "list_tree" \
+    "$KUGIRI list fixtures/selectors.md --tree"
+
+run_test "list_json_selector" \
+    "$KUGIRI list fixtures/attributes.md --format json --select '*[lang]'"
+
+# Drift detection (--check prints a diff and fails when the file would change)
+run_test "update_check_drift" \
+    "echo 'Updated content' | $KUGIRI update fixtures/basic.md --id section1 --check --color never 2>&1 || true"
+
+run_test "update_check_clean" \
+    "$KUGIRI extract fixtures/basic.md --id section1 | $KUGIRI update fixtures/basic.md --id section1 --check"
+
+# Diff preview and patches
+run_test "update_diff" \
+    "echo 'Updated content' | $KUGIRI update fixtures/basic.md --id section1 --diff --color never"
+
+run_test "remove_patch_stdout" \
+    "$KUGIRI remove fixtures/basic.md --id section2 --patch -"
+
+# Apply tests
+run_test "apply_diff" \
+    "$KUGIRI apply fixtures/manifest.yaml --diff --color never"
+
+run_test "apply_write" \
+    "rm -rf /tmp/kugiri_apply && mkdir /tmp/kugiri_apply && cp fixtures/manifest.yaml fixtures/basic.md fixtures/with_insert.md fixtures/content.txt /tmp/kugiri_apply && $KUGIRI apply /tmp/kugiri_apply/manifest.yaml -w && cat /tmp/kugiri_apply/basic.md /tmp/kugiri_apply/with_insert.md"
+
+run_test "apply_patch" \
+    "$KUGIRI apply fixtures/manifest.yaml --patch - | grep -e '^---' -e '^+++'"
+
+run_test "apply_check_drift" \
+    "$KUGIRI apply fixtures/manifest.yaml --check --color never 2>&1 | tail -n 1 || true"
+
+run_test "apply_missing_id" \
+    "$KUGIRI apply fixtures/manifest_missing.json --diff --color never 2>&1 || true"
+
+# Sync tests
+run_test "sync_basic" \
+    "$KUGIRI sync fixtures/commands.md"
+
+run_test "sync_check_drift" \
+    "$KUGIRI sync fixtures/commands.md --check --color never 2>&1 || true"
+
+run_test "sync_failing_command" \
+    "printf '<!-- KUGIRI-BEGIN: broken cmd=\"exit 7\" -->\\n<!-- KUGIRI-END: broken -->\\n' | $KUGIRI sync /dev/stdin --color never 2>&1 || true"
+
+# Include tests
+run_test "include_basic" \
+    "$KUGIRI include fixtures/snippets.md"
+
+run_test "include_check_drift" \
+    "$KUGIRI include fixtures/snippets.md --check --color never 2>&1 | tail -n 1 || true"
+
+run_test "include_outside_root" \
+    "$KUGIRI include fixtures/snippets.md --root golden --color never 2>&1 || true"
+
+# Push tests (in a subshell, as they run from a scratch directory)
+PUSH_SETUP="rm -rf /tmp/kugiri_push && mkdir /tmp/kugiri_push && cp fixtures/snippets.md fixtures/hash_style.py fixtures/nested.md /tmp/kugiri_push && $KUGIRI include /tmp/kugiri_push/snippets.md -w && sed -i.bak 's/0.0.0/1.0.0/' /tmp/kugiri_push/snippets.md && BIN=\$(realpath $KUGIRI) && cd /tmp/kugiri_push"
+
+run_test "push_diff" \
+    "($PUSH_SETUP && \$BIN push snippets.md --id constants --root . --diff --color never)"
+
+run_test "push_write" \
+    "($PUSH_SETUP && \$BIN push snippets.md --id constants --root . -w && cat hash_style.py && grep constants snippets.md)"
+
+run_test "push_check" \
+    "($PUSH_SETUP && { \$BIN push snippets.md --id constants --root . --check --color never > /dev/null; echo \"exit \$?\"; } && grep -c 1.0.0 hash_style.py || true)"
+
+run_test "push_conflict" \
+    "($PUSH_SETUP && sed -i.bak 's/VERSION/RELEASE/' hash_style.py && { \$BIN push snippets.md --id constants --root . --color never 2>&1 || true; })"
+
+# Move tests
+run_test "move_before_keeps_attributes" \
+    "$KUGIRI move fixtures/attributes.md --id go-example --before rust-example"
+
+run_test "move_into" \
+    "$KUGIRI move fixtures/basic.md --id section2 --into section1"
+
+run_test "move_after_reindents" \
+    "$KUGIRI move fixtures/with_insert.md --id section1 --after indented-insert"
+
+run_test "move_into_own_child" \
+    "$KUGIRI move fixtures/nested.md --id outer --into inner --color never 2>&1 || true"
+
+# Rename tests
+run_test "rename_basic" \
+    "$KUGIRI rename fixtures/with_insert.md --id section1 --to intro && $KUGIRI rename fixtures/with_insert.md --id section1 --to top-insert --color never 2>&1 || true"
+
+run_test "rename_all_files" \
+    "(rm -rf /tmp/kugiri_rename && mkdir /tmp/kugiri_rename && cp fixtures/snippets.md fixtures/hash_style.py /tmp/kugiri_rename && printf 'operations:\\n  - file: hash_style.py\\n    op: update\\n    id: constants\\n    command: echo VERSION = 2\\n' > /tmp/kugiri_rename/kugiri.yaml && BIN=\$(realpath $KUGIRI) && cd /tmp/kugiri_rename && \$BIN rename hash_style.py --id constants --to version --all-files --root . --diff --color never)"
+
+run_test "rename_all_files_patch" \
+    "(rm -rf /tmp/kugiri_rename_patch && mkdir -p /tmp/kugiri_rename_patch/.git /tmp/kugiri_rename_patch/docs && cp fixtures/hash_style.py /tmp/kugiri_rename_patch && cp fixtures/snippets.md /tmp/kugiri_rename_patch/docs && sed -i.bak 's|src=\"hash_style.py|src=\"../hash_style.py|' /tmp/kugiri_rename_patch/docs/snippets.md && rm /tmp/kugiri_rename_patch/docs/snippets.md.bak && BIN=\$(realpath $KUGIRI) && cd /tmp/kugiri_rename_patch && \$BIN rename hash_style.py --id constants --to version --all-files --patch - && { \$BIN rename hash_style.py --id constants --to version --all-files --check --color never 2>&1 | grep -e '^error' -e '^+++' || true; })"
+
+# Fmt tests
+run_test "fmt_basic" \
+    "$KUGIRI fmt fixtures/messy.md"
+
+run_test "fmt_patch_several_files" \
+    "$KUGIRI fmt fixtures/messy.md fixtures/selectors.md --patch - | grep -e '^---' -e '^+++'"
+
+run_test "fmt_check" \
+    "$KUGIRI fmt fixtures/basic.md fixtures/messy.md --check --color never 2>&1 || true"
+
+# Copy tests
+run_test "copy_between_files" \
+    "$KUGIRI copy --from fixtures/nested.md#inner --to fixtures/basic.md#section1"
+
+run_test "copy_create_after" \
+    "$KUGIRI copy --from fixtures/basic.md#section1 --to fixtures/basic.md#section3 --create-after section2"
+
+run_test "copy_missing_target" \
+    "$KUGIRI copy --from fixtures/basic.md#section1 --to fixtures/with_insert.md#section9 --color never 2>&1 || true"
+
+# Check tests
+run_test "check_broken" \
+    "$KUGIRI check fixtures/broken.md fixtures/basic.md --color never 2>&1 || true"
+
+run_test "check_fix" \
+    "cp fixtures/broken.md /tmp/kugiri_check_fix.md && { $KUGIRI check --fix -w --color never /tmp/kugiri_check_fix.md 2>&1 || true; } && cat /tmp/kugiri_check_fix.md"
+
+run_test "check_fix_check" \
+    "$KUGIRI check --fix --check fixtures/broken.md --color never 2>/dev/null; echo \"exit \$?\""
+
+# Diagnostics (the commands fail, so only their messages are compared)
+run_test "diagnostic_near_miss_id" \
+    "$KUGIRI extract fixtures/broken.md --id instal --color never 2>&1 || true"
+
+run_test "diagnostic_indent_mismatch" \
+    "$KUGIRI extract fixtures/broken.md --id foo --color never 2>&1 || true"
+
+run_test "diagnostic_unclosed_begin" \
+    "$KUGIRI extract fixtures/broken.md --id notes --color never 2>&1 || true"
+
 # Summary
 echo "========================"
 echo "Test Results:"
@@ -0,0 +1,26 @@
+use kugiri::io::write_output;
+use std::fs::{self, File};
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_write_output_skips_identical_content() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("README.md");
+    let path_str = path.to_str().unwrap();
+    fs::write(&path, "same\n").unwrap();
+
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+
+    write_output(path_str, "same\n", true).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), old);
+
+    write_output(path_str, "changed\n", true).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "changed\n");
+    assert_ne!(fs::metadata(&path).unwrap().modified().unwrap(), old);
+}